@@ -0,0 +1,9 @@
+alter table note add column updated_at timestamp;
+
+create table if not exists note_revision (
+    note_id text not null references note (note_id),
+    body text not null,
+    created_at timestamp not null default current_timestamp
+);
+
+create index if not exists idx_note_revision_note_id_created_at on note_revision (note_id, created_at desc);
\ No newline at end of file
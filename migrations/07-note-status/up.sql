@@ -0,0 +1,3 @@
+alter table note add column status text not null default 'published';
+
+create index if not exists idx_note_status_created_at_desc on note (status, created_at desc);
\ No newline at end of file
@@ -0,0 +1,3 @@
+alter table note add column publish_at timestamp;
+
+create index if not exists idx_note_status_publish_at on note (status, publish_at);
\ No newline at end of file
@@ -0,0 +1,7 @@
+create table if not exists note_tag (
+    note_id text not null references note (note_id),
+    tag text not null,
+    primary key (note_id, tag)
+);
+
+create index if not exists idx_note_tag_tag on note_tag (tag);
\ No newline at end of file
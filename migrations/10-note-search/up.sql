@@ -0,0 +1,15 @@
+create virtual table if not exists note_fts using fts5 (note_id unindexed, body);
+
+insert into note_fts (note_id, body) select note_id, body from note;
+
+create trigger if not exists note_fts_insert after insert on note begin
+    insert into note_fts (note_id, body) values (new.note_id, new.body);
+end;
+
+create trigger if not exists note_fts_update after update of body on note begin
+    update note_fts set body = new.body where note_id = new.note_id;
+end;
+
+create trigger if not exists note_fts_delete after delete on note begin
+    delete from note_fts where note_id = old.note_id;
+end;
\ No newline at end of file
@@ -0,0 +1,3 @@
+alter table image add column alt_text text not null default '';
+
+alter table image add column caption text not null default '';
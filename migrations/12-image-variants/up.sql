@@ -0,0 +1,9 @@
+alter table image add column width integer;
+
+alter table image add column height integer;
+
+create table if not exists image_variant (
+    image_id text not null references image (image_id),
+    width integer not null,
+    primary key (image_id, width)
+);
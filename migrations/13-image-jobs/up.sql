@@ -0,0 +1,9 @@
+alter table image add column status text not null default 'ready';
+
+alter table image add column error text;
+
+create table if not exists image_job (
+    image_id text primary key not null references image (image_id),
+    created_at timestamp not null default current_timestamp,
+    started_at timestamp
+);
@@ -0,0 +1,3 @@
+alter table image add column digest blob;
+
+create unique index if not exists idx_image_digest on image (digest);
@@ -0,0 +1,3 @@
+alter table image add column duration real;
+
+alter table image add column size integer;
@@ -0,0 +1,7 @@
+-- One-off data migrations which can't be expressed in SQL are run at startup while their names are
+-- in this table.
+create table if not exists backfill (
+    name text primary key not null
+);
+
+insert into backfill (name) values ('note_tag');
@@ -0,0 +1,2 @@
+-- Digest images uploaded before duplicates were detected. See ImageService::backfill_digests.
+insert into backfill (name) values ('image_digest');
@@ -0,0 +1 @@
+alter table image add column updated_at timestamp;
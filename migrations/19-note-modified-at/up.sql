@@ -0,0 +1,12 @@
+alter table note add column modified_at timestamp;
+
+update note set modified_at = coalesce(updated_at, created_at);
+
+create trigger if not exists note_modified_at_insert after insert on note begin
+    update note set modified_at = current_timestamp where note_id = new.note_id;
+end;
+
+create trigger if not exists note_modified_at_update
+after update of body, status, created_at, publish_at on note begin
+    update note set modified_at = current_timestamp where note_id = new.note_id;
+end;
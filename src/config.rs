@@ -1,8 +1,12 @@
-use std::{net::IpAddr, path::PathBuf};
+use std::{net::IpAddr, num::NonZeroUsize, path::PathBuf};
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use ipnet::IpNet;
+use tz::TimeZone;
 use url::Url;
 
+use crate::services::images::ImageProcessor;
+
 #[derive(Debug, Parser)]
 pub struct Config {
     /// The address on which to listen.
@@ -32,4 +36,46 @@ pub struct Config {
     /// The name of the person posting this crap.
     #[arg(long, default_value = "Luther Blissett", env("AUTHOR"))]
     pub author: String,
+
+    /// The IANA name of the time zone in which dates are displayed and notes are archived.
+    #[arg(long, default_value = "UTC", env("TIME_ZONE"), value_parser = parse_time_zone)]
+    pub time_zone: TimeZone,
+
+    /// The backend used to process uploaded images.
+    #[arg(long, value_enum, default_value = "magick", env("IMAGE_PROCESSOR"))]
+    pub image_processor: ImageProcessor,
+
+    /// The maximum number of images processed at a time.
+    #[arg(long, default_value = "2", env("IMAGE_WORKERS"))]
+    pub image_workers: NonZeroUsize,
+
+    /// Private address ranges (e.g. `10.0.0.0/8`) from which images may be downloaded.
+    #[arg(long, value_delimiter = ',', env("IMAGE_DOWNLOAD_ALLOWLIST"))]
+    pub image_download_allowlist: Vec<IpNet>,
+
+    /// Periodically delete unreferenced images, processed originals, and orphaned files.
+    #[arg(long, env("IMAGE_GC"))]
+    pub image_gc: bool,
+
+    /// The number of days an image or file is kept before it can be garbage collected.
+    #[arg(long, default_value = "7", env("IMAGE_GC_GRACE_DAYS"))]
+    pub image_gc_grace_days: u64,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Clone, Copy, Subcommand)]
+pub enum Command {
+    /// Delete unreferenced images, processed originals, and orphaned files, then exit.
+    Gc {
+        /// Report what would be deleted without deleting anything.
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+/// Parses an IANA time zone name (e.g. `America/Denver`) using the system's time zone database.
+fn parse_time_zone(name: &str) -> Result<TimeZone, tz::Error> {
+    if name == "UTC" { Ok(TimeZone::utc()) } else { TimeZone::from_posix_tz(name) }
 }
@@ -2,7 +2,10 @@ use clap::Parser;
 use tikv_jemallocator::Jemalloc;
 use tracing_subscriber::{EnvFilter, layer::SubscriberExt, util::SubscriberInitExt};
 
-use crate::{config::Config, web::App};
+use crate::{
+    config::{Command, Config},
+    web::App,
+};
 
 mod config;
 mod id;
@@ -25,7 +28,13 @@ async fn main() -> anyhow::Result<()> {
 
     // Parse the command line args.
     let config = Config::parse();
+    let command = config.command;
+    let app = App::new(config).await?;
 
-    // Spin up an HTTP server and listen for requests.
-    App::new(config).await?.serve().await
+    match command {
+        // Collect garbage and exit.
+        Some(Command::Gc { dry_run }) => app.collect_garbage(dry_run).await,
+        // Spin up an HTTP server and listen for requests.
+        None => app.serve().await,
+    }
 }
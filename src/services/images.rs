@@ -1,46 +1,106 @@
 use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Display},
     fs,
+    num::NonZeroUsize,
     path::{Path, PathBuf},
-    process::ExitStatus,
+    sync::Arc,
+    time::{Duration, SystemTime},
 };
 
 use anyhow::Context;
 use axum::{BoxError, body::Bytes};
 use futures::{Stream, TryStreamExt};
 use mime::Mime;
-use reqwest::header;
-use rusqlite::params;
+use rusqlite::{
+    OptionalExtension, Row, params,
+    types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef},
+};
+use sha2::{Digest, Sha256};
 use time::OffsetDateTime;
 use tokio::{
     fs::File,
     io::{self, BufWriter},
-    process::Command,
+    sync::Notify,
+    task::JoinSet,
 };
 use tokio_rusqlite::Connection;
-use tokio_util::io::StreamReader;
+use tokio_util::io::{ReaderStream, StreamReader};
 use url::Url;
 
+use self::processor::{extract_poster, probe_duration};
+pub use self::{fetcher::ImageFetcher, processor::ImageProcessor};
 use crate::id::PublicId;
 
-/// A service for adding news images.
+mod fetcher;
+mod processor;
+
+/// A service for adding new images, videos, and audio and processing them in the background.
 #[derive(Debug, Clone)]
 pub struct ImageService {
     db: Connection,
     data_dir: PathBuf,
+    processor: ImageProcessor,
+    fetcher: ImageFetcher,
+    jobs: Arc<Notify>,
 }
 
 impl ImageService {
-    /// Create a new [`ImageService`] using the given database and data directory.
-    pub fn new(db: Connection, data_dir: impl AsRef<Path>) -> Result<ImageService, io::Error> {
+    /// Create a new [`ImageService`] using the given database, data directory, image processor, and
+    /// image fetcher.
+    pub fn new(
+        db: Connection,
+        data_dir: impl AsRef<Path>,
+        processor: ImageProcessor,
+        fetcher: ImageFetcher,
+    ) -> Result<ImageService, io::Error> {
         let data_dir = data_dir.as_ref().to_path_buf();
         fs::create_dir_all(data_dir.join(IMAGES_DIR))?;
         fs::create_dir_all(data_dir.join(UPLOADS_DIR))?;
-        Ok(ImageService { db, data_dir })
+        Ok(ImageService { db, data_dir, processor, fetcher, jobs: Arc::new(Notify::new()) })
+    }
+
+    /// Returns a summary of the state of all images, which changes whenever an image is added,
+    /// described, processed, retried, or deleted.
+    #[tracing::instrument(skip(self), ret, err)]
+    pub async fn version(&self) -> Result<ImagesVersion, tokio_rusqlite::Error> {
+        Ok(self
+            .db
+            .call_unwrap(|conn| {
+                conn.prepare_cached(
+                    r#"
+                    select
+                      max(coalesce(updated_at, created_at)),
+                      count(*),
+                      count(*) filter (where status = ?)
+                    from image
+                    "#,
+                )?
+                .query_row(params![ImageStatus::Processing], |row| {
+                    Ok(ImagesVersion {
+                        modified_at: row.get(0)?,
+                        images: row.get(1)?,
+                        processing: row.get(2)?,
+                    })
+                })
+            })
+            .await?)
     }
 
     /// Returns the `n` most recent images, in reverse chronological order.
-    #[tracing::instrument(skip(self), err)]
     pub async fn most_recent(&self, n: u16) -> Result<Vec<Image>, tokio_rusqlite::Error> {
+        self.search(String::new(), 0, n).await
+    }
+
+    /// Returns up to `n` images whose original filenames contain the given text, in reverse
+    /// chronological order, skipping the first `offset` matches.
+    #[tracing::instrument(skip(self), err)]
+    pub async fn search(
+        &self,
+        filename: String,
+        offset: u32,
+        n: u16,
+    ) -> Result<Vec<Image>, tokio_rusqlite::Error> {
         Ok(self
             .db
             .call_unwrap(move |conn| {
@@ -49,31 +109,119 @@ impl ImageService {
                     select
                       image_id,
                       original_filename,
+                      content_type,
+                      alt_text,
+                      caption,
+                      status,
+                      error,
+                      duration,
+                      size,
                       created_at
                     from image
-                    order by created_at desc
-                    limit ?
+                    where instr(lower(original_filename), lower(?)) > 0
+                    order by created_at desc, image_id desc
+                    limit ? offset ?
                     "#,
                 )?
-                .query_map(params![n], |row| {
-                    Ok(Image {
-                        image_id: row.get(0)?,
-                        original_filename: row.get(1)?,
-                        created_at: row.get(2)?,
-                    })
-                })?
+                .query_map(params![filename, n, offset], |row| row.try_into())?
                 .collect::<Result<Vec<_>, _>>()
             })
             .await?)
     }
 
-    /// Processes the given stream as an image file and adds it to the database. Generates a main
-    /// WebP image for displaying in the feed and a thumbnail WebP image for the new note gallery.
+    /// Returns the image with the given ID, if any.
+    #[tracing::instrument(skip(self), err)]
+    pub async fn by_id(&self, image_id: &str) -> Result<Option<Image>, tokio_rusqlite::Error> {
+        let image_id = image_id.to_string();
+        Ok(self
+            .db
+            .call_unwrap(move |conn| {
+                conn.prepare_cached(
+                    r#"
+                    select
+                      image_id,
+                      original_filename,
+                      content_type,
+                      alt_text,
+                      caption,
+                      status,
+                      error,
+                      duration,
+                      size,
+                      created_at
+                    from image
+                    where image_id = ?
+                    "#,
+                )?
+                .query_row(params![image_id], |row| row.try_into())
+                .optional()
+            })
+            .await?)
+    }
+
+    /// Deletes an image and all of its files. Returns `false` if no such image exists.
+    #[tracing::instrument(skip(self), ret, err)]
+    pub async fn delete(&self, image_id: &str) -> Result<bool, anyhow::Error> {
+        let Some(image) = self.by_id(image_id).await? else {
+            return Ok(false);
+        };
+
+        self.db
+            .call_unwrap(move |conn| -> Result<(), rusqlite::Error> {
+                let tx = conn.transaction()?;
+                for table in ["image_job", "image_variant", "image"] {
+                    tx.prepare_cached(&format!("delete from {table} where image_id = ?"))?
+                        .execute(params![image.image_id])?;
+                }
+                tx.commit()
+            })
+            .await?;
+
+        let mut paths = self.processed_paths(&image.image_id, &image.content_type.parse()?);
+        paths.push(self.original(&image)?);
+        remove_files(&paths.iter().map(PathBuf::as_path).collect::<Vec<_>>()).await;
+
+        Ok(true)
+    }
+
+    /// Replaces the alt text and caption of an image. Returns `false` if no such image exists.
+    #[tracing::instrument(skip(self), ret, err)]
+    pub async fn describe(
+        &self,
+        image_id: &str,
+        alt_text: String,
+        caption: String,
+    ) -> Result<bool, tokio_rusqlite::Error> {
+        let image_id = image_id.to_string();
+        Ok(self
+            .db
+            .call_unwrap(move |conn| {
+                conn.prepare_cached(
+                    r#"
+                    update image
+                    set alt_text = ?, caption = ?, updated_at = current_timestamp
+                    where image_id = ?
+                    "#,
+                )?
+                .execute(params![alt_text, caption, image_id])
+            })
+            .await?
+            > 0)
+    }
+
+    /// Stores the given stream as an original image file, adds it to the database, and queues it for
+    /// processing. The image is marked as [`ImageStatus::Processing`] until a worker has generated
+    /// its main, thumbnail, and variant WebP images.
+    ///
+    /// If an image with the same contents already exists, its ID is returned instead. The given alt
+    /// text and caption are only used to fill in those of the existing image which are empty.
     #[tracing::instrument(skip(self, stream), ret(Display), err)]
     pub async fn add<S, E>(
         &self,
         original_filename: String,
         content_type: Mime,
+        alt_text: String,
+        caption: String,
         stream: S,
     ) -> Result<PublicId, anyhow::Error>
     where
@@ -83,64 +231,650 @@ impl ImageService {
         // Create a unique ID for the image.
         let image_id = PublicId::random();
 
-        // Stream the image file to the uploads directory.
-        let original_path = self
-            .data_dir
-            .join(UPLOADS_DIR)
-            .join(format!("{image_id}.orig.{}", content_type.subtype()));
-        stream_to_file(stream, &original_path).await.context("error streaming image")?;
+        // Stream the image file to the uploads directory and add it to the queue, unless an image
+        // with the same contents already exists.
+        let original_path = self.original_path(&image_id, &content_type);
+        let result = async {
+            let digest =
+                stream_to_file(stream, &original_path).await.context("error streaming image")?;
+            self.db
+                .call_unwrap(move |conn| -> Result<Option<PublicId>, rusqlite::Error> {
+                    let tx = conn.transaction()?;
+                    let existing = tx
+                        .prepare_cached(r#"select image_id from image where digest = ?"#)?
+                        .query_row(params![digest], |row| row.get(0))
+                        .optional()?;
+                    if let Some(existing) = existing {
+                        tx.prepare_cached(
+                            r#"
+                            update image
+                            set
+                              alt_text = iif(alt_text = '', ?, alt_text),
+                              caption = iif(caption = '', ?, caption),
+                              updated_at = current_timestamp
+                            where image_id = ?
+                            "#,
+                        )?
+                        .execute(params![alt_text, caption, existing])?;
+                        tx.commit()?;
+                        return Ok(Some(existing));
+                    }
+                    tx.prepare_cached(
+                        r#"
+                        insert into image
+                          (image_id, original_filename, content_type, alt_text, caption, status, digest)
+                        values (?, ?, ?, ?, ?, ?, ?)
+                        "#,
+                    )?
+                    .execute(params![
+                        image_id,
+                        original_filename,
+                        content_type.to_string(),
+                        alt_text,
+                        caption,
+                        ImageStatus::Processing,
+                        digest,
+                    ])?;
+                    tx.prepare_cached(r#"insert into image_job (image_id) values (?)"#)?
+                        .execute(params![image_id])?;
+                    tx.commit()?;
+                    Ok(None)
+                })
+                .await
+                .map_err(anyhow::Error::from)
+        }
+        .await;
 
-        // Generate a 600px-wide main WebP image.
-        let main_path = self.data_dir.join(IMAGES_DIR).join(main_filename(&image_id));
-        let main = process_image(&original_path, &main_path, "600");
+        match result {
+            Ok(None) => {
+                self.jobs.notify_one();
+                Ok(image_id)
+            }
+            // Don't keep a second copy of an existing image.
+            Ok(Some(existing)) => {
+                remove_files(&[&original_path]).await;
+                Ok(existing)
+            }
+            // Don't leave partial uploads lying around if anything failed.
+            Err(err) => {
+                remove_files(&[&original_path]).await;
+                Err(err)
+            }
+        }
+    }
 
-        // Generate a 100px-wide thumbnail WebP image.
-        let thumbnail_path = self.data_dir.join(IMAGES_DIR).join(thumbnail_filename(&image_id));
-        let thumbnail = process_image(&original_path, &thumbnail_path, "100");
+    /// Records the digests of images uploaded before digests were, once, so that they can be found
+    /// as duplicates. Images whose originals have already been garbage collected can't be digested,
+    /// and only the oldest of any existing duplicates is given a digest. Returns the number of
+    /// images digested, or `None` if the images have already been digested.
+    #[tracing::instrument(skip(self), ret, err)]
+    pub async fn backfill_digests(&self) -> Result<Option<usize>, anyhow::Error> {
+        let images = self
+            .db
+            .call_unwrap(|conn| -> Result<Option<Vec<(PublicId, String)>>, rusqlite::Error> {
+                let pending = conn
+                    .prepare_cached(r#"select 1 from backfill where name = 'image_digest'"#)?
+                    .exists([])?;
+                if !pending {
+                    return Ok(None);
+                }
+                conn.prepare_cached(
+                    r#"
+                    select image_id, content_type
+                    from image
+                    where digest is null
+                    order by created_at, rowid
+                    "#,
+                )?
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect::<Result<Vec<_>, _>>()
+                .map(Some)
+            })
+            .await?;
+        let Some(images) = images else {
+            return Ok(None);
+        };
 
-        // Wait for image processing to complete.
-        main.await.context("error generating main image")?;
-        thumbnail.await.context("error generating thumbnail image")?;
+        let mut n = 0;
+        for (image_id, content_type) in images {
+            let Ok(content_type) = content_type.parse::<Mime>() else {
+                continue;
+            };
+            let digest = match file_digest(&self.original_path(&image_id, &content_type)).await {
+                Ok(digest) => digest,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err).context("error digesting original"),
+            };
+            n += self
+                .db
+                .call_unwrap(move |conn| {
+                    conn.prepare_cached(
+                        r#"update or ignore image set digest = ? where image_id = ?"#,
+                    )?
+                    .execute(params![digest, image_id])
+                })
+                .await?;
+        }
 
-        // Add image to the database.
         self.db
+            .call_unwrap(|conn| {
+                conn.prepare_cached(r#"delete from backfill where name = 'image_digest'"#)?
+                    .execute([])
+            })
+            .await?;
+        Ok(Some(n))
+    }
+
+    /// Queues a failed image for processing again. Returns `false` if no such failed image exists.
+    #[tracing::instrument(skip(self), ret, err)]
+    pub async fn retry(&self, image_id: &str) -> Result<bool, tokio_rusqlite::Error> {
+        let image_id = image_id.to_string();
+        let retried = self
+            .db
+            .call_unwrap(move |conn| -> Result<bool, rusqlite::Error> {
+                let tx = conn.transaction()?;
+                let retried = tx
+                    .prepare_cached(
+                        r#"
+                        update image
+                        set status = ?, error = null, updated_at = current_timestamp
+                        where image_id = ? and status = ?
+                        "#,
+                    )?
+                    .execute(params![ImageStatus::Processing, image_id, ImageStatus::Failed])?
+                    > 0;
+                if retried {
+                    tx.prepare_cached(r#"insert into image_job (image_id) values (?)"#)?
+                        .execute(params![image_id])?;
+                }
+                tx.commit()?;
+                Ok(retried)
+            })
+            .await?;
+        if retried {
+            self.jobs.notify_one();
+        }
+        Ok(retried)
+    }
+
+    /// Runs an infinite asynchronous loop, processing queued images with at most `workers` images
+    /// being processed at a time.
+    pub async fn continuously_process(
+        self,
+        workers: NonZeroUsize,
+    ) -> Result<(), tokio_rusqlite::Error> {
+        // Jobs which were started but not finished were interrupted by a restart.
+        if let Err(err) = self
+            .db
+            .call_unwrap(|conn| {
+                conn.prepare_cached(r#"update image_job set started_at = null"#)?.execute([])
+            })
+            .await
+        {
+            tracing::error!(%err, "error releasing interrupted image jobs");
+        }
+
+        let mut tasks = JoinSet::new();
+        loop {
+            // Errors are logged by the workers, so only panics need to be logged here.
+            let retry = match self.claim_jobs(workers.get() - tasks.len()).await {
+                Ok(image_ids) => {
+                    for image_id in image_ids {
+                        let images = self.clone();
+                        tasks.spawn(async move { images.process(image_id).await });
+                    }
+                    false
+                }
+                Err(err) => {
+                    tracing::error!(%err, "error claiming image jobs");
+                    true
+                }
+            };
+
+            // Wait for either a new job, a free worker, or a retry after failing to claim jobs.
+            tokio::select! {
+                _ = self.jobs.notified() => {}
+                Some(result) = tasks.join_next() => {
+                    if let Err(err) = result {
+                        tracing::error!(%err, "image processing task failed");
+                    }
+                }
+                _ = tokio::time::sleep(CLAIM_RETRY_DELAY), if retry => {}
+            }
+        }
+    }
+
+    /// Processes all queued images, one at a time. Returns the number of images processed.
+    #[tracing::instrument(skip(self), ret, err)]
+    pub async fn process_queued(&self) -> Result<usize, tokio_rusqlite::Error> {
+        let mut n = 0;
+        while let Some(image_id) = self.claim_jobs(1).await?.pop() {
+            self.process(image_id).await?;
+            n += 1;
+        }
+        Ok(n)
+    }
+
+    /// Marks up to `n` queued jobs as started, returning their image IDs in the order they were
+    /// queued.
+    async fn claim_jobs(&self, n: usize) -> Result<Vec<PublicId>, tokio_rusqlite::Error> {
+        if n == 0 {
+            return Ok(vec![]);
+        }
+        Ok(self
+            .db
             .call_unwrap(move |conn| {
                 conn.prepare_cached(
                     r#"
-                    insert into image (image_id, original_filename, content_type)
-                    values (?, ?, ?)
+                    update image_job
+                    set started_at = current_timestamp
+                    where image_id in (
+                      select image_id
+                      from image_job
+                      where started_at is null
+                      order by created_at, rowid
+                      limit ?
+                    )
+                    returning image_id
                     "#,
                 )?
-                .execute(params![
-                    image_id,
-                    original_filename,
-                    content_type.to_string()
-                ])
+                .query_map(params![n], |row| row.get(0))?
+                .collect::<Result<Vec<_>, _>>()
+            })
+            .await?)
+    }
+
+    /// Processes a claimed job. If the job can't be finished, it's released so that it's retried
+    /// without waiting for a restart.
+    async fn process(&self, image_id: PublicId) -> Result<(), tokio_rusqlite::Error> {
+        let result = self.process_claimed(image_id).await;
+        if result.is_err() {
+            let released = self
+                .db
+                .call_unwrap(move |conn| {
+                    conn.prepare_cached(
+                        r#"update image_job set started_at = null where image_id = ?"#,
+                    )?
+                    .execute(params![image_id])
+                })
+                .await;
+            if let Err(err) = released {
+                tracing::error!(%err, %image_id, "error releasing image job");
+            }
+        }
+        result
+    }
+
+    /// Generates the main, thumbnail, and variant WebP images of a queued image, marking it as
+    /// ready or failed and removing its job. The WebP images of a video are generated from its
+    /// first frame, and the video itself is copied alongside them. Audio has no WebP images; it's
+    /// only copied and measured.
+    #[tracing::instrument(skip(self), err)]
+    async fn process_claimed(&self, image_id: PublicId) -> Result<(), tokio_rusqlite::Error> {
+        let Some(content_type) = self
+            .db
+            .call_unwrap(move |conn| {
+                conn.prepare_cached(r#"select content_type from image where image_id = ?"#)?
+                    .query_row(params![image_id], |row| row.get::<_, String>(0))
+                    .optional()
+            })
+            .await?
+        else {
+            // The image was deleted before it could be processed.
+            return Ok(());
+        };
+
+        // An unparseable content type leaves no original to process, failing the image.
+        let content_type = content_type.parse::<Mime>().unwrap_or(mime::APPLICATION_OCTET_STREAM);
+        let original_path = self.original_path(&image_id, &content_type);
+        let poster_path = self.data_dir.join(UPLOADS_DIR).join(format!("{image_id}.poster.png"));
+        let main_path = self.data_dir.join(IMAGES_DIR).join(main_filename(&image_id));
+        let thumbnail_path = self.data_dir.join(IMAGES_DIR).join(thumbnail_filename(&image_id));
+        let variant_paths = VARIANT_WIDTHS
+            .map(|width| self.data_dir.join(IMAGES_DIR).join(variant_filename(&image_id, width)));
+        let media_path = media_filename(&image_id, &content_type)
+            .map(|filename| self.data_dir.join(IMAGES_DIR).join(filename));
+        let result: Result<Processed, anyhow::Error> = async {
+            let mut processed = Processed::default();
+            if let Some(media_path) = &media_path {
+                processed.duration =
+                    Some(probe_duration(&original_path).await.context("error measuring duration")?);
+                processed.size = Some(
+                    tokio::fs::copy(&original_path, media_path)
+                        .await
+                        .context("error copying media")?,
+                );
+            }
+            if content_type.type_() == mime::AUDIO {
+                return Ok(processed);
+            }
+
+            // Use the first frame of a video as its poster image.
+            let input = if content_type.type_() == mime::VIDEO {
+                extract_poster(&original_path, &poster_path)
+                    .await
+                    .context("error extracting poster frame")?;
+                &poster_path
+            } else {
+                &original_path
+            };
+
+            // Generate a 600px-wide main WebP image, a 100px-wide thumbnail WebP image, and a
+            // WebP image of each variant width.
+            let mut outputs = vec![(main_path.as_path(), 600), (thumbnail_path.as_path(), 100)];
+            outputs.extend(variant_paths.iter().map(PathBuf::as_path).zip(VARIANT_WIDTHS));
+            let (width, height) =
+                self.processor.process(input, &outputs).await.context("error processing image")?;
+
+            // Don't keep variants which are wider than the original image, except the smallest.
+            let widths = VARIANT_WIDTHS
+                .into_iter()
+                .enumerate()
+                .filter(|&(i, w)| i == 0 || w <= width)
+                .map(|(_, w)| w)
+                .collect::<Vec<_>>();
+            let upscaled = variant_paths
+                .iter()
+                .zip(VARIANT_WIDTHS)
+                .filter(|(_, w)| !widths.contains(w))
+                .map(|(p, _)| p.as_path())
+                .collect::<Vec<_>>();
+            remove_files(&upscaled).await;
+
+            processed.dimensions = Some((width, height));
+            processed.widths = widths;
+            Ok(processed)
+        }
+        .await;
+        remove_files(&[&poster_path]).await;
+
+        // Don't leave partial outputs lying around if processing failed. The original is kept so
+        // that the image can be retried.
+        let mut outputs = vec![main_path.as_path(), &thumbnail_path];
+        outputs.extend(variant_paths.iter().map(PathBuf::as_path));
+        outputs.extend(media_path.as_deref());
+        let error = match &result {
+            Ok(_) => None,
+            Err(err) => {
+                remove_files(&outputs).await;
+                Some(format!("{err:#}"))
+            }
+        };
+
+        let exists = self
+            .db
+            .call_unwrap(move |conn| -> Result<bool, rusqlite::Error> {
+                let tx = conn.transaction()?;
+                let updated = match result {
+                    Ok(processed) => {
+                        let updated = tx
+                            .prepare_cached(
+                                r#"
+                                update image
+                                set
+                                  status = ?,
+                                  error = null,
+                                  width = ?,
+                                  height = ?,
+                                  duration = ?,
+                                  size = ?,
+                                  updated_at = current_timestamp
+                                where image_id = ?
+                                "#,
+                            )?
+                            .execute(params![
+                                ImageStatus::Ready,
+                                processed.dimensions.map(|(w, _)| w),
+                                processed.dimensions.map(|(_, h)| h),
+                                processed.duration,
+                                processed.size,
+                                image_id,
+                            ])?;
+                        tx.prepare_cached(r#"delete from image_variant where image_id = ?"#)?
+                            .execute(params![image_id])?;
+                        if updated > 0 {
+                            let mut insert = tx.prepare_cached(
+                                r#"insert into image_variant (image_id, width) values (?, ?)"#,
+                            )?;
+                            for width in processed.widths {
+                                insert.execute(params![image_id, width])?;
+                            }
+                        }
+                        updated
+                    }
+                    Err(_) => tx
+                        .prepare_cached(
+                            r#"
+                            update image
+                            set status = ?, error = ?, updated_at = current_timestamp
+                            where image_id = ?
+                            "#,
+                        )?
+                        .execute(params![ImageStatus::Failed, error, image_id])?,
+                };
+                tx.prepare_cached(r#"delete from image_job where image_id = ?"#)?
+                    .execute(params![image_id])?;
+                tx.commit()?;
+                Ok(updated > 0)
+            })
+            .await?;
+
+        // The image was deleted while it was being processed.
+        if !exists {
+            remove_files(&outputs).await;
+        }
+
+        Ok(())
+    }
+
+    /// Finds images which aren't embedded in any of the given URIs of processed images, originals of
+    /// processed images, and files which don't belong to any image, and deletes them unless
+    /// `dry_run` is set. Anything created within the grace period is left alone.
+    #[tracing::instrument(skip(self, srcs), err)]
+    pub async fn collect_garbage(
+        &self,
+        srcs: Vec<String>,
+        grace_period: Duration,
+        dry_run: bool,
+    ) -> Result<Garbage, anyhow::Error> {
+        let referenced =
+            srcs.iter().filter_map(|src| parse_processed_src(src)).collect::<HashSet<_>>();
+        // A grace period reaching back before the epoch keeps everything.
+        let cutoff = SystemTime::now().checked_sub(grace_period).unwrap_or(SystemTime::UNIX_EPOCH);
+        let images = self
+            .db
+            .call_unwrap(|conn| {
+                conn.prepare_cached(
+                    r#"
+                    select
+                      image_id,
+                      original_filename,
+                      content_type,
+                      alt_text,
+                      caption,
+                      status,
+                      error,
+                      duration,
+                      size,
+                      created_at
+                    from image
+                    "#,
+                )?
+                .query_map([], |row| row.try_into())?
+                .collect::<Result<Vec<Image>, _>>()
             })
             .await?;
 
-        Ok(image_id)
+        let mut garbage = Garbage::default();
+        let image_ids = images.iter().map(|i| i.image_id).collect::<HashSet<_>>();
+        for image in images {
+            if image.status == ImageStatus::Processing || image.created_at > cutoff {
+                continue;
+            }
+
+            let original = self.original(&image)?;
+            if !referenced.contains(&image.image_id) {
+                garbage.bytes += file_size(&original).await;
+                for path in self.processed_paths(&image.image_id, &image.content_type.parse()?) {
+                    garbage.bytes += file_size(&path).await;
+                }
+                garbage.unreferenced.push(image);
+            } else if image.status == ImageStatus::Ready
+                && tokio::fs::try_exists(
+                    self.data_dir.join(IMAGES_DIR).join(processed_filename(&image)?),
+                )
+                .await?
+                && tokio::fs::try_exists(&original).await?
+            {
+                garbage.bytes += file_size(&original).await;
+                garbage.originals.push(original);
+            }
+        }
+
+        // Files which aren't named after an existing image are partial uploads or leftovers from
+        // failed processing.
+        for dir in [IMAGES_DIR, UPLOADS_DIR] {
+            let mut entries = tokio::fs::read_dir(self.data_dir.join(dir)).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let metadata = entry.metadata().await?;
+                let image_id = entry
+                    .file_name()
+                    .to_str()
+                    .and_then(|name| name.split('.').next())
+                    .and_then(|id| id.parse::<PublicId>().ok());
+                if metadata.is_file()
+                    && metadata.modified()? < cutoff
+                    && !image_id.is_some_and(|id| image_ids.contains(&id))
+                {
+                    garbage.bytes += metadata.len();
+                    garbage.orphans.push(entry.path());
+                }
+            }
+        }
+
+        if !dry_run {
+            for image in &garbage.unreferenced {
+                self.delete(&image.image_id.to_string()).await?;
+            }
+            let paths = garbage.originals.iter().chain(&garbage.orphans);
+            remove_files(&paths.map(PathBuf::as_path).collect::<Vec<_>>()).await;
+        }
+
+        Ok(garbage)
     }
 
     /// Downloads the image at the given URL and adds it via [`add`].
     #[tracing::instrument(skip(self), fields(image_url=%image_url), ret(Display), err)]
-    pub async fn download(&self, image_url: Url) -> Result<PublicId, anyhow::Error> {
+    pub async fn download(
+        &self,
+        image_url: Url,
+        alt_text: String,
+        caption: String,
+    ) -> Result<PublicId, anyhow::Error> {
         let original_filename = image_url.to_string();
+        let (content_type, body) = self.fetcher.fetch(image_url).await?;
+        self.add(original_filename, content_type, alt_text, caption, body).await
+    }
 
-        // Start the request to download the image.
-        let image = reqwest::get(image_url).await.context("error downloading image")?;
-        anyhow::ensure!(image.status().is_success(), "error response: {}", image.status());
+    /// Returns the dimensions, variants, and videos of the images with the given main image URIs,
+    /// and the audio with the given audio URIs. Unknown URIs, unprocessed audio, and images uploaded
+    /// before variants were generated are ignored.
+    #[tracing::instrument(skip(self), err)]
+    pub async fn responsive(
+        &self,
+        srcs: Vec<String>,
+    ) -> Result<ResponsiveImages, tokio_rusqlite::Error> {
+        Ok(self
+            .db
+            .call_unwrap(move |conn| -> Result<ResponsiveImages, rusqlite::Error> {
+                let mut images = ResponsiveImages::default();
+                let mut select = conn.prepare_cached(
+                    r#"
+                    select width, height, content_type, size
+                    from image
+                    where image_id = ? and width is not null and height is not null
+                    "#,
+                )?;
+                let mut select_audio = conn.prepare_cached(
+                    r#"
+                    select content_type, size
+                    from image
+                    where image_id = ? and status = ?
+                    "#,
+                )?;
+                let mut variants = conn.prepare_cached(
+                    r#"select width from image_variant where image_id = ? order by width"#,
+                )?;
+                for src in srcs {
+                    if let Some(image_id) = parse_audio_src(&src) {
+                        if let Some(audio) = select_audio
+                            .query_row(params![image_id, ImageStatus::Ready], |row| {
+                                Ok(Media {
+                                    src: src.clone(),
+                                    content_type: row.get(0)?,
+                                    size: row.get(1)?,
+                                })
+                            })
+                            .optional()?
+                        {
+                            images.audio.insert(src, audio);
+                        }
+                        continue;
+                    }
+                    let Some(image_id) = parse_main_src(&src) else {
+                        continue;
+                    };
+                    let Some((width, height, content_type, size)) = select
+                        .query_row(params![image_id], |row| {
+                            Ok((row.get(0)?, row.get(1)?, row.get::<_, String>(2)?, row.get(3)?))
+                        })
+                        .optional()?
+                    else {
+                        continue;
+                    };
+                    let widths = variants
+                        .query_map(params![image_id], |row| row.get(0))?
+                        .collect::<Result<Vec<u32>, _>>()?;
+                    let srcset = widths
+                        .into_iter()
+                        .map(|w| format!("/{}/{} {w}w", IMAGES_DIR, variant_filename(&image_id, w)))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    let video = content_type.parse::<Mime>().ok().and_then(|content_type| {
+                        let filename = media_filename(&image_id, &content_type)?;
+                        Some(Media {
+                            src: format!("/{IMAGES_DIR}/{filename}"),
+                            content_type: content_type.essence_str().to_string(),
+                            size,
+                        })
+                    });
+                    images.images.insert(src, ResponsiveImage { width, height, srcset, video });
+                }
+                Ok(images)
+            })
+            .await?)
+    }
+
+    /// The path of the original version of an image.
+    fn original_path(&self, image_id: &PublicId, content_type: &Mime) -> PathBuf {
+        self.data_dir.join(UPLOADS_DIR).join(format!("{image_id}.orig.{}", content_type.subtype()))
+    }
 
-        // Get the image's content type.
-        let content_type = image
-            .headers()
-            .get(header::CONTENT_TYPE)
-            .and_then(|v| v.to_str().ok())
-            .ok_or_else(|| anyhow::anyhow!("no Content-Type header"))
-            .and_then(|s| s.parse::<Mime>().context("invalid Content-Type header"))?;
+    /// Returns the path of the original version of the given image.
+    pub fn original(&self, image: &Image) -> Result<PathBuf, anyhow::Error> {
+        Ok(self.original_path(&image.image_id, &image.content_type.parse()?))
+    }
 
-        // Add the response body as an image.
-        self.add(original_filename, content_type, image.bytes_stream()).await
+    /// The paths of the main, thumbnail, variant, and video or audio versions of an image.
+    fn processed_paths(&self, image_id: &PublicId, content_type: &Mime) -> Vec<PathBuf> {
+        let images_dir = self.data_dir.join(IMAGES_DIR);
+        let mut paths = vec![
+            images_dir.join(main_filename(image_id)),
+            images_dir.join(thumbnail_filename(image_id)),
+        ];
+        paths.extend(VARIANT_WIDTHS.map(|w| images_dir.join(variant_filename(image_id, w))));
+        paths.extend(media_filename(image_id, content_type).map(|f| images_dir.join(f)));
+        paths
     }
 
     /// Returns the directory containing the processed images.
@@ -149,13 +883,44 @@ impl ImageService {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq)]
 pub struct Image {
-    image_id: PublicId,
+    pub image_id: PublicId,
     pub original_filename: String,
+    pub content_type: String,
+    /// A description of the image for people who can't see it.
+    pub alt_text: String,
+    /// A caption to display alongside the image.
+    pub caption: String,
+    pub status: ImageStatus,
+    /// Why the image couldn't be processed, if it failed.
+    pub error: Option<String>,
+    /// The duration of a video or audio, in seconds.
+    pub duration: Option<f64>,
+    /// The size of a video or audio file, in bytes.
+    pub size: Option<u64>,
     pub created_at: OffsetDateTime,
 }
 
+impl<'stmt> TryFrom<&'stmt Row<'stmt>> for Image {
+    type Error = rusqlite::Error;
+
+    fn try_from(row: &'stmt Row<'stmt>) -> Result<Self, Self::Error> {
+        Ok(Image {
+            image_id: row.get(0)?,
+            original_filename: row.get(1)?,
+            content_type: row.get(2)?,
+            alt_text: row.get(3)?,
+            caption: row.get(4)?,
+            status: row.get(5)?,
+            error: row.get(6)?,
+            duration: row.get(7)?,
+            size: row.get(8)?,
+            created_at: row.get(9)?,
+        })
+    }
+}
+
 impl Image {
     /// The URI for the main version of the image.
     pub fn main_src(&self) -> String {
@@ -166,6 +931,113 @@ impl Image {
     pub fn thumbnail_src(&self) -> String {
         format!("/{}/{}", IMAGES_DIR, thumbnail_filename(&self.image_id))
     }
+
+    /// The URI for the playable version of the image, if it's a video.
+    pub fn video_src(&self) -> Option<String> {
+        self.media_src(mime::VIDEO)
+    }
+
+    /// The URI for the playable version of the image, if it's audio.
+    pub fn audio_src(&self) -> Option<String> {
+        self.media_src(mime::AUDIO)
+    }
+
+    /// The URI to embed in notes: the audio itself for audio, the main version for anything else.
+    pub fn embed_src(&self) -> String {
+        self.audio_src().unwrap_or_else(|| self.main_src())
+    }
+
+    fn media_src(&self, type_: mime::Name<'_>) -> Option<String> {
+        let content_type = self.content_type.parse::<Mime>().ok()?;
+        if content_type.type_() != type_ {
+            return None;
+        }
+        Some(format!("/{IMAGES_DIR}/{}", media_filename(&self.image_id, &content_type)?))
+    }
+}
+
+/// A summary of the state of all images. See [`ImageService::version`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImagesVersion {
+    /// When an image was most recently added or changed, if ever.
+    pub modified_at: Option<OffsetDateTime>,
+    /// The number of images.
+    pub images: u64,
+    /// The number of images which are being processed.
+    pub processing: u64,
+}
+
+/// Images and files which are no longer needed.
+#[derive(Debug, Default)]
+pub struct Garbage {
+    /// Images which aren't embedded in any note.
+    pub unreferenced: Vec<Image>,
+    /// Originals of images which have already been processed.
+    pub originals: Vec<PathBuf>,
+    /// Files which don't belong to any image.
+    pub orphans: Vec<PathBuf>,
+    /// The total size of the garbage, in bytes.
+    pub bytes: u64,
+}
+
+impl Display for Garbage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for image in &self.unreferenced {
+            writeln!(f, "unreferenced image: {} ({})", image.image_id, image.original_filename)?;
+        }
+        for path in &self.originals {
+            writeln!(f, "processed original: {}", path.display())?;
+        }
+        for path in &self.orphans {
+            writeln!(f, "orphaned file: {}", path.display())?;
+        }
+        writeln!(
+            f,
+            "{} images, {} originals, {} orphaned files, {} bytes",
+            self.unreferenced.len(),
+            self.originals.len(),
+            self.orphans.len(),
+            self.bytes
+        )
+    }
+}
+
+/// The processing status of an [`Image`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageStatus {
+    /// The image is queued or being processed.
+    Processing,
+    /// The image has been processed and can be used in notes.
+    Ready,
+    /// The image couldn't be processed.
+    Failed,
+}
+
+impl ImageStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ImageStatus::Processing => "processing",
+            ImageStatus::Ready => "ready",
+            ImageStatus::Failed => "failed",
+        }
+    }
+}
+
+impl FromSql for ImageStatus {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value.as_str()? {
+            "processing" => Ok(ImageStatus::Processing),
+            "ready" => Ok(ImageStatus::Ready),
+            "failed" => Ok(ImageStatus::Failed),
+            s => Err(FromSqlError::Other(format!("invalid image status: {s}").into())),
+        }
+    }
+}
+
+impl ToSql for ImageStatus {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
 }
 
 /// The canonical filename of the main version of an image.
@@ -173,19 +1045,141 @@ fn main_filename(image_id: &PublicId) -> String {
     format!("{image_id}.main.webp")
 }
 
+/// The dimensions and variants of a set of images, keyed by the URI of each image's main version,
+/// and a set of audio, keyed by URI.
+#[derive(Debug, Default)]
+pub struct ResponsiveImages {
+    images: HashMap<String, ResponsiveImage>,
+    audio: HashMap<String, Media>,
+}
+
+impl ResponsiveImages {
+    /// Returns the responsive variants of the image with the given main image URI, if any.
+    pub fn get(&self, src: &str) -> Option<&ResponsiveImage> {
+        self.images.get(src)
+    }
+
+    /// Returns the audio with the given URI, if any.
+    pub fn audio(&self, src: &str) -> Option<&Media> {
+        self.audio.get(src)
+    }
+
+    /// Returns the video or audio embedded with the given URI, if any.
+    pub fn media(&self, src: &str) -> Option<&Media> {
+        self.get(src).and_then(|image| image.video.as_ref()).or_else(|| self.audio(src))
+    }
+}
+
+/// The intrinsic dimensions and variants of an image.
+#[derive(Debug)]
+pub struct ResponsiveImage {
+    /// The width of the original image, in pixels.
+    pub width: u32,
+    /// The height of the original image, in pixels.
+    pub height: u32,
+    /// A `srcset` attribute value listing each variant of the image.
+    pub srcset: String,
+    /// The video the image is a poster frame of, if any.
+    pub video: Option<Media>,
+}
+
+/// A video or audio file.
+#[derive(Debug)]
+pub struct Media {
+    /// The URI of the file.
+    pub src: String,
+    /// The content type of the file.
+    pub content_type: String,
+    /// The size of the file, in bytes, if known.
+    pub size: Option<u64>,
+}
+
+/// The results of processing an image, video, or audio file.
+#[derive(Debug, Default)]
+struct Processed {
+    /// The width and height of the image, or of the video's poster frame.
+    dimensions: Option<(u32, u32)>,
+    /// The widths of the variants which were kept.
+    widths: Vec<u32>,
+    /// The duration of the video or audio, in seconds.
+    duration: Option<f64>,
+    /// The size of the copied video or audio file, in bytes.
+    size: Option<u64>,
+}
+
+/// The canonical filename of a variant of an image with the given width.
+fn variant_filename(image_id: &PublicId, width: u32) -> String {
+    format!("{image_id}.{width}w.webp")
+}
+
+/// The canonical filename of the playable version of an image, if it's a video or audio.
+fn media_filename(image_id: &PublicId, content_type: &Mime) -> Option<String> {
+    let kind = if content_type.type_() == mime::VIDEO {
+        "video"
+    } else if content_type.type_() == mime::AUDIO {
+        "audio"
+    } else {
+        return None;
+    };
+    let extension = match content_type.essence_str() {
+        "video/quicktime" => "mov",
+        "video/x-matroska" => "mkv",
+        "video/x-msvideo" => "avi",
+        "video/ogg" => "ogv",
+        "video/mpeg" => "mpg",
+        "audio/mpeg" | "audio/mp3" => "mp3",
+        "audio/mp4" | "audio/m4a" | "audio/x-m4a" => "m4a",
+        "audio/wav" | "audio/wave" | "audio/x-wav" => "wav",
+        _ => content_type.subtype().as_str(),
+    };
+    Some(format!("{image_id}.{kind}.{extension}"))
+}
+
+/// The filename of the file which proves an image has been processed: the video or audio file
+/// for video or audio, the main version for anything else.
+fn processed_filename(image: &Image) -> Result<String, anyhow::Error> {
+    let content_type = image.content_type.parse()?;
+    Ok(media_filename(&image.image_id, &content_type)
+        .unwrap_or_else(|| main_filename(&image.image_id)))
+}
+
+/// Parses the ID of an audio file from its URI.
+fn parse_audio_src(src: &str) -> Option<PublicId> {
+    let (image_id, _) = src.strip_prefix(&format!("/{IMAGES_DIR}/"))?.split_once(".audio.")?;
+    image_id.parse().ok()
+}
+
+/// Parses the ID of an image from the URI of any of its processed files: its main, thumbnail, or
+/// variant WebP images, or its video or audio.
+fn parse_processed_src(src: &str) -> Option<PublicId> {
+    let (image_id, suffix) = src.strip_prefix(&format!("/{IMAGES_DIR}/"))?.split_once('.')?;
+    let processed = matches!(suffix, "main.webp" | "thumb.webp")
+        || suffix.strip_suffix("w.webp").is_some_and(|w| w.parse::<u32>().is_ok())
+        || suffix.strip_prefix("video.").or_else(|| suffix.strip_prefix("audio.")).is_some();
+    processed.then(|| image_id.parse().ok()).flatten()
+}
+
+/// Parses the ID of an image from the URI of its main version.
+fn parse_main_src(src: &str) -> Option<PublicId> {
+    src.strip_prefix(&format!("/{IMAGES_DIR}/"))?.strip_suffix(".main.webp")?.parse().ok()
+}
+
 /// The canonical filename of the thumbnail version of an image.
 fn thumbnail_filename(image_id: &PublicId) -> String {
     format!("{image_id}.thumb.webp")
 }
 
+/// Writes the given stream to a file, returning the SHA-256 digest of its contents.
 #[tracing::instrument(skip(stream), err)]
-async fn stream_to_file<S, E>(stream: S, path: &Path) -> Result<(), io::Error>
+async fn stream_to_file<S, E>(stream: S, path: &Path) -> Result<Vec<u8>, io::Error>
 where
     S: Stream<Item = Result<Bytes, E>>,
     E: Into<BoxError>,
 {
-    // Convert the stream into an `AsyncRead`.
-    let body_with_io_error = stream.map_err(|err| io::Error::other(err));
+    // Convert the stream into an `AsyncRead`, hashing each chunk as it passes through.
+    let mut hasher = Sha256::new();
+    let body_with_io_error =
+        stream.map_err(|err| io::Error::other(err)).inspect_ok(|chunk| hasher.update(chunk));
     let body_reader = StreamReader::new(body_with_io_error);
     futures::pin_mut!(body_reader);
 
@@ -195,27 +1189,255 @@ where
     // Copy the body into the file.
     io::copy(&mut body_reader, &mut file).await?;
 
-    Ok(())
+    Ok(hasher.finalize().to_vec())
 }
 
-#[tracing::instrument(ret(Display), err)]
-async fn process_image<'a>(
-    input: &'a Path,
-    output: &'a Path,
-    geometry: &'static str,
-) -> io::Result<ExitStatus> {
-    Command::new("magick")
-        .arg(input)
-        .arg("-auto-orient")
-        .arg("-strip")
-        .arg("-thumbnail")
-        .arg(geometry)
-        .arg(output)
-        .spawn()?
-        .wait()
-        .await
+/// Returns the SHA-256 digest of the contents of the given file.
+async fn file_digest(path: &Path) -> Result<Vec<u8>, io::Error> {
+    let mut hasher = Sha256::new();
+    ReaderStream::new(File::open(path).await?)
+        .try_for_each(|chunk| {
+            hasher.update(chunk);
+            futures::future::ok(())
+        })
+        .await?;
+    Ok(hasher.finalize().to_vec())
 }
 
+/// Returns the size of the given file, or zero if it doesn't exist.
+async fn file_size(path: &Path) -> u64 {
+    tokio::fs::metadata(path).await.map(|m| m.len()).unwrap_or(0)
+}
+
+/// Removes the given files, ignoring any which don't exist.
+async fn remove_files(paths: &[&Path]) {
+    for path in paths {
+        match tokio::fs::remove_file(path).await {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                tracing::warn!(?path, %err, "error removing file");
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The widths of the responsive variants generated for each image.
+const VARIANT_WIDTHS: [u32; 3] = [300, 600, 1200];
+
 const UPLOADS_DIR: &str = "uploads";
 
 const IMAGES_DIR: &str = "images";
+
+/// How long to wait before trying to claim image jobs again after failing to.
+const CLAIM_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+#[cfg(test)]
+mod tests {
+    use futures::stream;
+
+    use super::*;
+    use crate::test::TestEnv;
+
+    #[tokio::test]
+    async fn continuously_processing() -> Result<(), anyhow::Error> {
+        let env = TestEnv::new().await?;
+        let images = env.state.images.clone();
+        let worker = tokio::spawn(images.clone().continuously_process(NonZeroUsize::MIN));
+
+        for width in [10, 20, 30] {
+            add_png(&images, width).await?;
+        }
+
+        assert_eq!(images.most_recent(10).await?.len(), 3);
+        tokio::time::timeout(Duration::from_secs(30), async {
+            while images.most_recent(3).await?.iter().any(|i| i.status != ImageStatus::Ready) {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+            Ok::<_, anyhow::Error>(())
+        })
+        .await??;
+        worker.abort();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn continuously_processing_through_errors() -> Result<(), anyhow::Error> {
+        let env = TestEnv::new().await?;
+        let images = env.state.images.clone();
+        env.db
+            .call_unwrap(|conn| {
+                conn.execute_batch(
+                    r#"
+                    create trigger fail_claim before update on image_job
+                    begin select raise(abort, 'database is busy'); end;
+                    "#,
+                )
+            })
+            .await?;
+        let worker = tokio::spawn(images.clone().continuously_process(NonZeroUsize::MIN));
+        let image_id = add_png(&images, 10).await?;
+
+        // The worker keeps retrying until it can claim the job.
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!worker.is_finished());
+        env.db.call_unwrap(|conn| conn.execute_batch("drop trigger fail_claim")).await?;
+        tokio::time::timeout(Duration::from_secs(30), async {
+            while images.by_id(&image_id.to_string()).await?.map(|i| i.status)
+                != Some(ImageStatus::Ready)
+            {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+            Ok::<_, anyhow::Error>(())
+        })
+        .await??;
+        worker.abort();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn duplicate_images() -> Result<(), anyhow::Error> {
+        let env = TestEnv::new().await?;
+        let images = &env.state.images;
+        let alt_text = |image_id: PublicId| async move {
+            let image = images.by_id(&image_id.to_string()).await?;
+            Ok::<_, anyhow::Error>(image.expect("should exist").alt_text)
+        };
+
+        // Duplicates fill in missing alt text, but don't replace it.
+        let image_id = add_png(images, 10).await?;
+        assert_eq!(add_described_png(images, 10, "A box.").await?, image_id);
+        assert_eq!(alt_text(image_id).await?, "A box.");
+        assert_eq!(add_described_png(images, 10, "A rectangle.").await?, image_id);
+        assert_eq!(alt_text(image_id).await?, "A box.");
+
+        // Images uploaded before digests were recorded are digested once.
+        let old_id = add_png(images, 20).await?;
+        let dupe_id = add_png(images, 30).await?;
+        env.db.call_unwrap(|conn| conn.execute_batch(r#"update image set digest = null"#)).await?;
+        let dupe_path = images.original_path(&dupe_id, &mime::IMAGE_PNG);
+        fs::copy(images.original_path(&old_id, &mime::IMAGE_PNG), &dupe_path)?;
+        assert_eq!(images.backfill_digests().await?, Some(2));
+        assert_eq!(images.backfill_digests().await?, None);
+        assert_eq!(add_png(images, 20).await?, old_id);
+        assert_eq!(add_png(images, 10).await?, image_id);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn releasing_failed_jobs() -> Result<(), anyhow::Error> {
+        let env = TestEnv::new().await?;
+        let images = &env.state.images;
+        let image_id = add_png(images, 10).await?;
+        env.db
+            .call_unwrap(|conn| {
+                conn.execute_batch(
+                    r#"
+                    create trigger fail_update before update of status on image
+                    begin select raise(abort, 'database is broken'); end;
+                    "#,
+                )
+            })
+            .await?;
+
+        assert!(images.process_queued().await.is_err());
+        let started_at = env
+            .db
+            .call_unwrap(move |conn| {
+                conn.query_row(
+                    r#"select started_at from image_job where image_id = ?"#,
+                    params![image_id],
+                    |row| row.get::<_, Option<OffsetDateTime>>(0),
+                )
+            })
+            .await?;
+        assert_eq!(started_at, None);
+
+        env.db.call_unwrap(|conn| conn.execute_batch("drop trigger fail_update")).await?;
+        assert_eq!(images.process_queued().await?, 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn collecting_garbage() -> Result<(), anyhow::Error> {
+        let env = TestEnv::new().await?;
+        let images = &env.state.images;
+
+        let used = add_png(images, 10).await?;
+        let unused = add_png(images, 20).await?;
+        images.process_queued().await?;
+        env.state
+            .notes
+            .create(format!("![Used](http://example.com/images/{used}.main.webp)"))
+            .await?;
+
+        let images_dir = env.temp_dir.path().join(IMAGES_DIR);
+        let uploads_dir = env.temp_dir.path().join(UPLOADS_DIR);
+        fs::write(images_dir.join("garbage.webp"), b"garbage")?;
+        fs::write(uploads_dir.join(format!("{}.orig.png", PublicId::random())), b"garbage")?;
+
+        // Nothing is collected within the grace period.
+        let garbage = env.state.collect_garbage(false).await?;
+        assert!(garbage.unreferenced.is_empty());
+        assert!(garbage.originals.is_empty());
+        assert!(garbage.orphans.is_empty());
+
+        // Grace periods which reach back before the epoch keep everything.
+        let garbage = images.collect_garbage(vec![], Duration::MAX, true).await?;
+        assert!(garbage.unreferenced.is_empty());
+        assert!(garbage.orphans.is_empty());
+
+        let garbage = images.collect_garbage(vec![], Duration::ZERO, true).await?;
+        assert_eq!(garbage.unreferenced.len(), 2);
+
+        // Embedding any processed file of an image counts as a reference to it.
+        for src in [format!("/images/{unused}.600w.webp"), format!("/images/{unused}.thumb.webp")] {
+            let garbage = images.collect_garbage(vec![src], Duration::ZERO, true).await?;
+            assert_eq!(garbage.unreferenced.iter().map(|i| i.image_id).collect::<Vec<_>>(), [used]);
+        }
+        assert_eq!(parse_processed_src(&format!("/images/{used}.video.mp4")), Some(used));
+        assert_eq!(parse_processed_src(&format!("/images/{used}.audio.mp3")), Some(used));
+        assert_eq!(parse_processed_src(&format!("/images/{used}.orig.png")), None);
+
+        let srcs = vec![format!("/images/{used}.main.webp")];
+        let garbage = images.collect_garbage(srcs.clone(), Duration::ZERO, true).await?;
+        assert_eq!(garbage.unreferenced.iter().map(|i| i.image_id).collect::<Vec<_>>(), [unused]);
+        assert_eq!(garbage.originals, [uploads_dir.join(format!("{used}.orig.png"))]);
+        assert_eq!(garbage.orphans.len(), 2);
+        assert!(garbage.bytes > 0);
+        assert!(fs::exists(images_dir.join("garbage.webp"))?);
+        assert_eq!(images.most_recent(10).await?.len(), 2);
+
+        images.collect_garbage(srcs, Duration::ZERO, false).await?;
+        let recent = images.most_recent(10).await?;
+        assert_eq!(recent.iter().map(|i| i.image_id).collect::<Vec<_>>(), [used]);
+        assert!(fs::exists(images_dir.join(main_filename(&used)))?);
+        assert!(!fs::exists(images_dir.join(main_filename(&unused)))?);
+        assert!(!fs::exists(images_dir.join("garbage.webp"))?);
+        assert_eq!(fs::read_dir(&uploads_dir)?.count(), 0);
+
+        Ok(())
+    }
+
+    /// Adds a blank PNG image of the given width.
+    async fn add_png(images: &ImageService, width: u32) -> Result<PublicId, anyhow::Error> {
+        add_described_png(images, width, "").await
+    }
+
+    /// Adds a blank PNG image of the given width with the given alt text.
+    async fn add_described_png(
+        images: &ImageService,
+        width: u32,
+        alt_text: &str,
+    ) -> Result<PublicId, anyhow::Error> {
+        let mut png = Vec::new();
+        image::RgbImage::new(width, 10)
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)?;
+        let body = stream::iter([Ok::<_, io::Error>(Bytes::from(png))]);
+        let name = format!("{width}.png");
+        images.add(name, mime::IMAGE_PNG, alt_text.into(), String::new(), body).await
+    }
+}
@@ -0,0 +1,324 @@
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::Context;
+use axum::{BoxError, body::Bytes};
+use futures::{Stream, StreamExt, TryStreamExt, stream};
+use ipnet::IpNet;
+use mime::Mime;
+use reqwest::{
+    Client,
+    dns::{Addrs, Name, Resolve, Resolving},
+    redirect,
+};
+use url::{Host, Url};
+
+/// An HTTP client for downloading images which refuses to connect to private addresses, follows a
+/// limited number of redirects, and limits the size of response bodies.
+#[derive(Debug, Clone)]
+pub struct ImageFetcher {
+    client: Client,
+    allowlist: Arc<[IpNet]>,
+}
+
+impl ImageFetcher {
+    /// Create a new [`ImageFetcher`] which identifies itself with the given base URL and allows
+    /// connections to private addresses in the given allowlist.
+    pub fn new(base_url: &Url, allowlist: Vec<IpNet>) -> Result<ImageFetcher, reqwest::Error> {
+        let allowlist: Arc<[IpNet]> = allowlist.into();
+        let redirects = allowlist.clone();
+        let client = Client::builder()
+            .user_agent(format!(
+                "{}/{} (+{base_url})",
+                env!("CARGO_PKG_NAME"),
+                env!("CARGO_PKG_VERSION")
+            ))
+            .no_proxy()
+            .dns_resolver(Arc::new(PublicResolver { allowlist: allowlist.clone() }))
+            .redirect(redirect::Policy::custom(move |attempt| {
+                if attempt.previous().len() >= MAX_REDIRECTS {
+                    attempt.error("too many redirects")
+                } else if !is_allowed_url(attempt.url(), &redirects) {
+                    let error = format!("blocked redirect to {}", attempt.url());
+                    attempt.error(error)
+                } else {
+                    attempt.follow()
+                }
+            }))
+            .connect_timeout(Duration::from_secs(10))
+            .read_timeout(Duration::from_secs(30))
+            .build()?;
+        Ok(ImageFetcher { client, allowlist })
+    }
+
+    /// Starts downloading the image at the given URL. Returns the content type of the image, based
+    /// on its contents rather than the response headers, and a stream of the image's bytes.
+    pub async fn fetch(
+        &self,
+        url: Url,
+    ) -> Result<(Mime, impl Stream<Item = Result<Bytes, BoxError>> + Send + use<>), anyhow::Error>
+    {
+        anyhow::ensure!(is_allowed_url(&url, &self.allowlist), "blocked URL: {url}");
+
+        // Start the request to download the image.
+        let resp = self.client.get(url).send().await.context("error downloading image")?;
+        anyhow::ensure!(resp.status().is_success(), "error response: {}", resp.status());
+        if let Some(len) = resp.content_length() {
+            anyhow::ensure!(len <= MAX_SIZE, "image too large: {len} bytes");
+        }
+
+        // Fail the stream once it exceeds the maximum size.
+        let mut size = 0;
+        let mut body = resp.bytes_stream().map(move |chunk| {
+            let chunk = chunk?;
+            size += chunk.len() as u64;
+            if size > MAX_SIZE {
+                return Err(BoxError::from(format!("image too large: over {MAX_SIZE} bytes")));
+            }
+            Ok(chunk)
+        });
+
+        // Buffer enough of the body to recognize the image's format.
+        let mut head = Vec::new();
+        while head.len() < SNIFF_LEN {
+            match body.try_next().await.map_err(|err| anyhow::anyhow!(err))? {
+                Some(chunk) => head.extend_from_slice(&chunk),
+                None => break,
+            }
+        }
+        let content_type = sniff(&head).context("not a supported image")?;
+
+        Ok((content_type, stream::once(async { Ok(Bytes::from(head)) }).chain(body)))
+    }
+}
+
+/// A DNS resolver which filters out any addresses which aren't public or in the allowlist.
+struct PublicResolver {
+    allowlist: Arc<[IpNet]>,
+}
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let allowlist = self.allowlist.clone();
+        Box::pin(async move {
+            let addrs = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| is_allowed(addr.ip(), &allowlist))
+                .collect::<Vec<_>>();
+            if addrs.is_empty() {
+                return Err(format!("{} has no public addresses", name.as_str()).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Returns whether the given URL is an HTTP(S) URL whose host is a domain name, which is checked
+/// when it's resolved, or an allowed IP address.
+fn is_allowed_url(url: &Url, allowlist: &[IpNet]) -> bool {
+    if !matches!(url.scheme(), "http" | "https") {
+        return false;
+    }
+    match url.host() {
+        Some(Host::Domain(_)) => true,
+        Some(Host::Ipv4(ip)) => is_allowed(ip.into(), allowlist),
+        Some(Host::Ipv6(ip)) => is_allowed(ip.into(), allowlist),
+        None => false,
+    }
+}
+
+/// Returns whether the given address is public or in the allowlist.
+fn is_allowed(ip: IpAddr, allowlist: &[IpNet]) -> bool {
+    allowlist.iter().any(|net| net.contains(&ip)) || is_public(ip)
+}
+
+/// Returns whether the given address is globally routable.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let shared = IpNet::new(Ipv4Addr::new(100, 64, 0, 0).into(), 10).expect("valid prefix");
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                || ip.octets()[0] == 0
+                || shared.contains(&IpAddr::V4(ip)))
+        }
+        IpAddr::V6(ip) => match embedded_ipv4(ip) {
+            Some(ip) => is_public(ip.into()),
+            None => {
+                let [a, b, ..] = ip.segments();
+                !(ip.is_unspecified()
+                    || ip.is_loopback()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local()
+                    || ip.is_multicast()
+                    || (a == 0x2001 && b == 0xdb8))
+            }
+        },
+    }
+}
+
+/// Returns the IPv4 address embedded in an IPv4-mapped (`::ffff:0:0/96`), IPv4-translated
+/// (`::ffff:0:0:0/96`), IPv4-compatible (`::/96`), or NAT64 (`64:ff9b::/96`) IPv6 address.
+fn embedded_ipv4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    let [.., a, b, c, d] = ip.octets();
+    match ip.segments() {
+        [0, 0, 0, 0, 0xffff | 0, 0, _, _]
+        | [0, 0, 0, 0, 0, 0xffff, _, _]
+        | [0x64, 0xff9b, 0, 0, 0, 0, _, _] => Some(Ipv4Addr::new(a, b, c, d)),
+        _ => None,
+    }
+}
+
+/// Returns the content type of the given image data, based on its magic bytes.
+fn sniff(head: &[u8]) -> Option<Mime> {
+    if let Ok(format) = image::guess_format(head) {
+        return format.to_mime_type().parse().ok();
+    }
+
+    // HEIF-based formats are ISO base media files, identified by the brand in their `ftyp` box.
+    match head.get(4..12)? {
+        b"ftypheic" | b"ftypheix" | b"ftyphevc" | b"ftypmif1" | b"ftypmsf1" => {
+            "image/heic".parse().ok()
+        }
+        b"ftypavif" => "image/avif".parse().ok(),
+        _ => None,
+    }
+}
+
+/// The maximum number of redirects to follow.
+const MAX_REDIRECTS: usize = 5;
+
+/// The maximum size of a downloaded image, in bytes.
+const MAX_SIZE: u64 = 32 * 1024 * 1024;
+
+/// The number of bytes needed to recognize an image's format.
+const SNIFF_LEN: usize = 32;
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        Router,
+        http::header,
+        response::Redirect,
+        routing::{get, get_service},
+    };
+    use tokio::net::TcpListener;
+    use tower_http::services::ServeFile;
+
+    use super::*;
+
+    async fn serve() -> Result<Url, anyhow::Error> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = Url::parse(&format!("http://{}/", listener.local_addr()?))?;
+        let elsewhere = format!("http://127.0.0.2:{}/logo.webp", listener.local_addr()?.port());
+        let app = Router::new()
+            .route_service("/logo.webp", get_service(ServeFile::new("yellhole.webp")))
+            .route(
+                "/mislabeled",
+                get(|| async {
+                    (
+                        [(header::CONTENT_TYPE, "text/plain")],
+                        std::fs::read("yellhole.webp").unwrap(),
+                    )
+                }),
+            )
+            .route("/page.html", get(|| async { "<!doctype html><html></html>" }))
+            .route("/huge", get(|| async { vec![0u8; MAX_SIZE as usize + 1] }))
+            .route("/loop", get(|| async { Redirect::temporary("/loop") }))
+            .route("/elsewhere", get(|| async move { Redirect::temporary(&elsewhere) }));
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        Ok(url)
+    }
+
+    #[tokio::test]
+    async fn fetching_images() -> Result<(), anyhow::Error> {
+        let url = serve().await?;
+        let base_url = "http://example.com".parse()?;
+        let fetcher = ImageFetcher::new(&base_url, vec!["127.0.0.1/32".parse()?])?;
+
+        let (content_type, body) = fetcher.fetch(url.join("/logo.webp")?).await?;
+        assert_eq!(content_type, "image/webp".parse::<Mime>()?);
+        let body = body.try_collect::<Vec<_>>().await.map_err(|err| anyhow::anyhow!(err))?;
+        assert_eq!(body.concat(), std::fs::read("yellhole.webp")?);
+
+        let (content_type, _) = fetcher.fetch(url.join("/mislabeled")?).await?;
+        assert_eq!(content_type, "image/webp".parse::<Mime>()?);
+
+        let err = fetcher.fetch(url.join("/page.html")?).await.err().expect("should fail");
+        assert_eq!(err.to_string(), "not a supported image");
+
+        let err = fetcher.fetch(url.join("/huge")?).await.err().expect("should fail");
+        assert!(err.to_string().starts_with("image too large"));
+
+        let err = fetcher.fetch(url.join("/loop")?).await.err().expect("should fail");
+        assert!(format!("{err:#}").contains("too many redirects"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn blocking_private_addresses() -> Result<(), anyhow::Error> {
+        let url = serve().await?;
+        let port = url.port().expect("should have a port");
+        let base_url = "http://example.com".parse()?;
+        let fetcher = ImageFetcher::new(&base_url, vec![])?;
+
+        let err = fetcher.fetch(url.join("/logo.webp")?).await.err().expect("should fail");
+        assert!(err.to_string().starts_with("blocked URL"));
+
+        let localhost = format!("http://localhost:{port}/logo.webp").parse()?;
+        let err = fetcher.fetch(localhost).await.err().expect("should fail");
+        assert!(format!("{err:#}").contains("no public addresses"));
+
+        let err = fetcher.fetch("file:///etc/passwd".parse()?).await.err().expect("should fail");
+        assert!(err.to_string().starts_with("blocked URL"));
+
+        // Redirects to addresses outside the allowlist are blocked.
+        let fetcher = ImageFetcher::new(&base_url, vec!["127.0.0.1/32".parse()?])?;
+        let err = fetcher.fetch(url.join("/elsewhere")?).await.err().expect("should fail");
+        assert!(format!("{err:#}").contains("blocked redirect to http://127.0.0.2"));
+
+        assert!(!is_public("10.1.2.3".parse()?));
+        assert!(!is_public("169.254.169.254".parse()?));
+        assert!(!is_public("100.64.0.1".parse()?));
+        assert!(!is_public("::ffff:192.168.0.1".parse()?));
+        assert!(!is_public("fd00::1".parse()?));
+        assert!(!is_public("fe80::1".parse()?));
+        assert!(is_public("93.184.216.34".parse()?));
+        assert!(is_public("2606:2800:220:1::".parse()?));
+
+        Ok(())
+    }
+
+    #[test]
+    fn blocking_embedded_private_addresses() -> Result<(), anyhow::Error> {
+        // NAT64
+        assert!(!is_public("64:ff9b::10.1.2.3".parse()?));
+        assert!(!is_public("64:ff9b::127.0.0.1".parse()?));
+        assert!(is_public("64:ff9b::93.184.216.34".parse()?));
+
+        // IPv4-compatible
+        assert!(!is_public("::127.0.0.1".parse()?));
+        assert!(!is_public("::169.254.169.254".parse()?));
+        assert!(is_public("::93.184.216.34".parse()?));
+
+        // IPv4-translated
+        assert!(!is_public("::ffff:0:10.1.2.3".parse()?));
+        assert!(!is_public("::ffff:0:127.0.0.1".parse()?));
+        assert!(is_public("::ffff:0:93.184.216.34".parse()?));
+
+        // Documentation
+        assert!(!is_public("2001:db8::1".parse()?));
+        assert!(!is_public("2001:db8:ffff::93.184.216.34".parse()?));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,301 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+    process::{ExitStatus, Stdio},
+};
+
+use anyhow::Context;
+use clap::ValueEnum;
+use image::{
+    AnimationDecoder, DynamicImage, GenericImageView, ImageFormat, ImageReader,
+    codecs::{gif::GifDecoder, png::PngDecoder, webp::WebPDecoder},
+    imageops::FilterType,
+};
+use thiserror::Error;
+use tokio::{process::Command, task};
+
+/// The backend used to resize images and convert them to WebP.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ImageProcessor {
+    /// Shell out to ImageMagick's `magick` binary for every image.
+    Magick,
+    /// Process common formats (JPEG, PNG, GIF, WebP, BMP, TIFF) in-process, falling back to
+    /// ImageMagick for anything else (e.g. HEIC) and for animated images. The in-process WebP
+    /// encoder is lossless, so its images are several times larger than ImageMagick's.
+    Native,
+}
+
+impl ImageProcessor {
+    /// Generates a WebP image of each of the given widths from the input image, writing each to its
+    /// paired path. The images are oriented according to their EXIF metadata, which is stripped.
+    /// Animated images are converted to animated WebP images. Returns the width and height of the
+    /// oriented input image.
+    pub async fn process(
+        self,
+        input: &Path,
+        outputs: &[(&Path, u32)],
+    ) -> Result<(u32, u32), anyhow::Error> {
+        if self == ImageProcessor::Native
+            && let Some(format) = native_format(input)?
+            && !is_animated(input, format)?
+        {
+            let input = input.to_path_buf();
+            let outputs =
+                outputs.iter().map(|&(path, width)| (path.to_path_buf(), width)).collect();
+            return task::spawn_blocking(move || process_native(&input, outputs))
+                .await
+                .context("error joining image processing task")?;
+        }
+
+        for result in futures::future::join_all(
+            outputs.iter().map(|&(output, width)| process_magick(input, output, width)),
+        )
+        .await
+        {
+            result?;
+        }
+        identify_magick(input).await
+    }
+}
+
+/// An error returned when an image can't be processed, e.g. because it's corrupt or in an
+/// unsupported format.
+#[derive(Debug, Error)]
+pub enum ProcessingError {
+    /// ImageMagick exited unsuccessfully.
+    #[error("magick exited with {status}: {stderr}")]
+    Magick {
+        /// The exit status of the `magick` process.
+        status: ExitStatus,
+        /// The standard error output of the `magick` process.
+        stderr: String,
+    },
+
+    /// One of FFmpeg's tools exited unsuccessfully.
+    #[error("{program} exited with {status}: {stderr}")]
+    Ffmpeg {
+        /// The name of the tool, e.g. `ffmpeg` or `ffprobe`.
+        program: &'static str,
+        /// The exit status of the process.
+        status: ExitStatus,
+        /// The standard error output of the process.
+        stderr: String,
+    },
+
+    /// The image couldn't be decoded or encoded in-process.
+    #[error(transparent)]
+    Native(#[from] image::ImageError),
+}
+
+/// Returns the format of the given image if it can be decoded in-process.
+fn native_format(input: &Path) -> Result<Option<ImageFormat>, anyhow::Error> {
+    let format = ImageReader::open(input)
+        .and_then(ImageReader::with_guessed_format)
+        .context("error reading image")?
+        .format();
+    Ok(format.filter(ImageFormat::reading_enabled))
+}
+
+/// Returns whether the given image has more than one frame. Only ImageMagick can preserve the
+/// animation of an animated image.
+fn is_animated(input: &Path, format: ImageFormat) -> Result<bool, anyhow::Error> {
+    let reader = BufReader::new(File::open(input).context("error reading image")?);
+    let animated = match format {
+        ImageFormat::Gif => {
+            GifDecoder::new(reader).map_err(ProcessingError::from)?.into_frames().take(2).count()
+                > 1
+        }
+        ImageFormat::Png => {
+            PngDecoder::new(reader).and_then(|d| d.is_apng()).map_err(ProcessingError::from)?
+        }
+        ImageFormat::WebP => {
+            WebPDecoder::new(reader).map_err(ProcessingError::from)?.has_animation()
+        }
+        _ => false,
+    };
+    Ok(animated)
+}
+
+#[tracing::instrument(err)]
+fn process_native(input: &Path, outputs: Vec<(PathBuf, u32)>) -> Result<(u32, u32), anyhow::Error> {
+    let mut decoder = ImageReader::open(input)
+        .and_then(ImageReader::with_guessed_format)
+        .context("error reading image")?
+        .into_decoder()
+        .map_err(ProcessingError::from)?;
+    let orientation =
+        image::ImageDecoder::orientation(&mut decoder).map_err(ProcessingError::from)?;
+    let mut image = DynamicImage::from_decoder(decoder).map_err(ProcessingError::from)?;
+    image.apply_orientation(orientation);
+
+    for (output, width) in outputs {
+        let height = (u64::from(image.height()) * u64::from(width)
+            / u64::from(image.width().max(1)))
+        .clamp(1, u32::MAX.into()) as u32;
+        let resized = image.resize_exact(width, height, FilterType::Lanczos3);
+
+        // The WebP encoder only supports 8-bit RGB and RGBA images.
+        let resized = if resized.color().has_alpha() {
+            DynamicImage::ImageRgba8(resized.into_rgba8())
+        } else {
+            DynamicImage::ImageRgb8(resized.into_rgb8())
+        };
+
+        let mut file = BufWriter::new(File::create(&output).context("error creating image")?);
+        resized.write_to(&mut file, ImageFormat::WebP).map_err(ProcessingError::from)?;
+    }
+    Ok(image.dimensions())
+}
+
+#[tracing::instrument(err)]
+async fn process_magick(input: &Path, output: &Path, width: u32) -> Result<(), anyhow::Error> {
+    run_magick(
+        Command::new("magick")
+            .arg(input)
+            .arg("-coalesce")
+            .arg("-auto-orient")
+            .arg("-strip")
+            .arg("-thumbnail")
+            .arg(width.to_string())
+            .arg(output),
+    )
+    .await?;
+    Ok(())
+}
+
+#[tracing::instrument(ret, err)]
+async fn identify_magick(input: &Path) -> Result<(u32, u32), anyhow::Error> {
+    // Only the first frame of e.g. an animated GIF is measured.
+    let mut frame = input.as_os_str().to_owned();
+    frame.push("[0]");
+    let stdout = run_magick(
+        Command::new("magick")
+            .arg(frame)
+            .arg("-auto-orient")
+            .arg("-format")
+            .arg("%w %h")
+            .arg("info:"),
+    )
+    .await?;
+    let (width, height) =
+        stdout.trim().split_once(' ').ok_or_else(|| anyhow::anyhow!("bad dimensions: {stdout}"))?;
+    Ok((width.parse()?, height.parse()?))
+}
+
+/// Extracts the first frame of the given video as a PNG image, oriented according to the video's
+/// rotation metadata.
+#[tracing::instrument(err)]
+pub async fn extract_poster(input: &Path, output: &Path) -> Result<(), anyhow::Error> {
+    run_ffmpeg(
+        "ffmpeg",
+        Command::new("ffmpeg")
+            .args(["-v", "error", "-y", "-i"])
+            .arg(input)
+            .args(["-frames:v", "1", "-f", "image2", "-c:v", "png"])
+            .arg(output),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Returns the duration of the given audio or video file, in seconds.
+#[tracing::instrument(ret, err)]
+pub async fn probe_duration(input: &Path) -> Result<f64, anyhow::Error> {
+    let stdout = run_ffmpeg(
+        "ffprobe",
+        Command::new("ffprobe")
+            .args(["-v", "error", "-show_entries", "format=duration", "-of", "csv=p=0"])
+            .arg(input),
+    )
+    .await?;
+    stdout.trim().parse().with_context(|| format!("bad duration: {stdout}"))
+}
+
+/// Runs the given FFmpeg tool, returning its standard output.
+async fn run_ffmpeg(program: &'static str, command: &mut Command) -> Result<String, anyhow::Error> {
+    let output = command
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .with_context(|| format!("error running {program}"))?;
+    if !output.status.success() {
+        return Err(ProcessingError::Ffmpeg {
+            program,
+            status: output.status,
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        }
+        .into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Runs the given `magick` command, returning its standard output.
+async fn run_magick(command: &mut Command) -> Result<String, anyhow::Error> {
+    let output = command.stdin(Stdio::null()).output().await.context("error running magick")?;
+    if !output.status.success() {
+        return Err(ProcessingError::Magick {
+            status: output.status,
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        }
+        .into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn native_processing() -> Result<(), anyhow::Error> {
+        let dir = TempDir::new()?;
+        let (main, thumbnail) = (dir.path().join("main.webp"), dir.path().join("thumb.webp"));
+        let dimensions = ImageProcessor::Native
+            .process(Path::new("yellhole.webp"), &[(&main, 600), (&thumbnail, 100)])
+            .await?;
+
+        assert_eq!(dimensions, image::image_dimensions("yellhole.webp")?);
+        assert_eq!(image::image_dimensions(&main)?.0, 600);
+        assert_eq!(image::image_dimensions(&thumbnail)?.0, 100);
+        assert_eq!(
+            ImageReader::open(&main)?.with_guessed_format()?.format(),
+            Some(ImageFormat::WebP)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn detecting_animation() -> Result<(), anyhow::Error> {
+        let dir = TempDir::new()?;
+        let (still, animated) = (dir.path().join("still.gif"), dir.path().join("animated.gif"));
+        let frame = || image::Frame::new(image::RgbaImage::new(10, 10));
+        image::codecs::gif::GifEncoder::new(File::create(&still)?).encode_frames([frame()])?;
+        image::codecs::gif::GifEncoder::new(File::create(&animated)?)
+            .encode_frames([frame(), frame()])?;
+
+        assert!(!is_animated(&still, ImageFormat::Gif)?);
+        assert!(is_animated(&animated, ImageFormat::Gif)?);
+        assert!(!is_animated(Path::new("yellhole.webp"), ImageFormat::WebP)?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn native_processing_failure() -> Result<(), anyhow::Error> {
+        let dir = TempDir::new()?;
+        let input = dir.path().join("corrupt.webp");
+        std::fs::write(&input, b"RIFF\x10\0\0\0WEBPVP8 not an image")?;
+
+        let err = ImageProcessor::Native
+            .process(&input, &[(&dir.path().join("main.webp"), 600)])
+            .await
+            .expect_err("should fail to process");
+        assert!(matches!(err.downcast_ref(), Some(ProcessingError::Native(_))));
+
+        Ok(())
+    }
+}
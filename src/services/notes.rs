@@ -1,23 +1,39 @@
-use std::ops::Range;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Display},
+    ops::Range,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
 
-use pulldown_cmark::{Event, Options, Parser, Tag};
-use rusqlite::{OptionalExtension, Row, params};
-use time::{Date, OffsetDateTime, Time};
+use pulldown_cmark::{CowStr, Event, LinkType, Options, Parser, Tag, TagEnd, TextMergeStream};
+use rusqlite::{
+    OptionalExtension, Row, params,
+    types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef},
+};
+use time::{Date, Month, OffsetDateTime, UtcOffset};
 use tokio_rusqlite::Connection;
+use tz::TimeZone;
 use url::Url;
 
-use crate::id::PublicId;
+use crate::{
+    id::PublicId,
+    services::images::{ResponsiveImage, ResponsiveImages},
+};
 
 /// A service for creating and viewing [`Note`]s.
 #[derive(Debug, Clone)]
 pub struct NoteService {
     db: Connection,
+    time_zone: Arc<TimeZone>,
 }
 
 impl NoteService {
-    /// Create a new [`NoteService`] using the given database.
-    pub fn new(db: Connection) -> NoteService {
-        NoteService { db }
+    /// Create a new [`NoteService`] using the given database. Notes are grouped into weeks and
+    /// months using the given time zone.
+    pub fn new(db: Connection, time_zone: TimeZone) -> NoteService {
+        NoteService { db, time_zone: Arc::new(time_zone) }
     }
 
     /// Create a new [`Note`], returning the new note's ID.
@@ -25,14 +41,295 @@ impl NoteService {
     pub async fn create(&self, body: String) -> Result<PublicId, tokio_rusqlite::Error> {
         let note_id = PublicId::random();
         self.db
-            .call_unwrap(move |conn| {
-                conn.prepare_cached(r#"insert into note (note_id, body) values (?, ?)"#)?
-                    .execute(params![note_id, body])
+            .call_unwrap(move |conn| -> Result<(), rusqlite::Error> {
+                let tx = conn.transaction()?;
+                tx.prepare_cached(r#"insert into note (note_id, body) values (?, ?)"#)?
+                    .execute(params![note_id, body])?;
+                index_tags(&tx, &note_id, &body)?;
+                tx.commit()
             })
             .await?;
         Ok(note_id)
     }
 
+    /// Save the body of a draft [`Note`], creating a new draft if no ID is given. Returns the
+    /// draft's ID.
+    #[tracing::instrument(skip(self, body), ret(Display), err)]
+    pub async fn save_draft(
+        &self,
+        draft_id: Option<PublicId>,
+        body: String,
+    ) -> Result<PublicId, tokio_rusqlite::Error> {
+        let draft_id = draft_id.unwrap_or_else(PublicId::random);
+        self.db
+            .call_unwrap(move |conn| -> Result<(), rusqlite::Error> {
+                let tx = conn.transaction()?;
+                let saved = tx
+                    .prepare_cached(
+                        r#"
+                        insert into note (note_id, body, status) values (?, ?, ?)
+                        on conflict (note_id) do update
+                        set body = excluded.body, updated_at = current_timestamp
+                        where note.status = excluded.status
+                        "#,
+                    )?
+                    .execute(params![draft_id, body, NoteStatus::Draft])?;
+                if saved > 0 {
+                    index_tags(&tx, &draft_id, &body)?;
+                }
+                tx.commit()
+            })
+            .await?;
+        Ok(draft_id)
+    }
+
+    /// Publish a draft [`Note`] with the given body. Returns `false` if no such draft exists.
+    #[tracing::instrument(skip(self, body), ret, err)]
+    pub async fn publish(
+        &self,
+        draft_id: PublicId,
+        body: String,
+    ) -> Result<bool, tokio_rusqlite::Error> {
+        Ok(self
+            .db
+            .call_unwrap(move |conn| -> Result<bool, rusqlite::Error> {
+                let tx = conn.transaction()?;
+                let published = tx
+                    .prepare_cached(
+                        r#"
+                        update note
+                        set body = ?, status = ?, created_at = current_timestamp, updated_at = null
+                        where note_id = ? and status = ?
+                        "#,
+                    )?
+                    .execute(params![body, NoteStatus::Published, draft_id, NoteStatus::Draft])?;
+                if published > 0 {
+                    index_tags(&tx, &draft_id, &body)?;
+                }
+                tx.commit()?;
+                Ok(published > 0)
+            })
+            .await?)
+    }
+
+    /// Schedule a [`Note`] with the given body to be published at the given time, creating a new
+    /// note if no ID is given. Returns the scheduled note's ID.
+    #[tracing::instrument(skip(self, body), ret(Display), err)]
+    pub async fn schedule(
+        &self,
+        note_id: Option<PublicId>,
+        body: String,
+        publish_at: OffsetDateTime,
+    ) -> Result<PublicId, tokio_rusqlite::Error> {
+        let note_id = note_id.unwrap_or_else(PublicId::random);
+        self.db
+            .call_unwrap(move |conn| -> Result<(), rusqlite::Error> {
+                let tx = conn.transaction()?;
+                let scheduled = tx
+                    .prepare_cached(
+                        r#"
+                        insert into note (note_id, body, status, publish_at)
+                        values (?, ?, ?, datetime(?))
+                        on conflict (note_id) do update
+                        set body = excluded.body, status = excluded.status,
+                            publish_at = excluded.publish_at, updated_at = current_timestamp
+                        where note.status in (?, ?)
+                        "#,
+                    )?
+                    .execute(params![
+                        note_id,
+                        body,
+                        NoteStatus::Scheduled,
+                        publish_at,
+                        NoteStatus::Draft,
+                        NoteStatus::Scheduled
+                    ])?;
+                if scheduled > 0 {
+                    index_tags(&tx, &note_id, &body)?;
+                }
+                tx.commit()
+            })
+            .await?;
+        Ok(note_id)
+    }
+
+    /// Cancel the scheduled publication of a [`Note`], returning it to the drafts. Returns `false`
+    /// if no such scheduled note exists.
+    #[tracing::instrument(skip(self), ret, err)]
+    pub async fn cancel_schedule(&self, note_id: &str) -> Result<bool, tokio_rusqlite::Error> {
+        let note_id = note_id.to_string();
+        Ok(self
+            .db
+            .call_unwrap(move |conn| {
+                conn.prepare_cached(
+                    r#"
+                    update note
+                    set status = ?, publish_at = null, updated_at = current_timestamp
+                    where note_id = ? and status = ?
+                    "#,
+                )?
+                .execute(params![
+                    NoteStatus::Draft,
+                    note_id,
+                    NoteStatus::Scheduled
+                ])
+            })
+            .await?
+            > 0)
+    }
+
+    /// Runs an infinite asynchronous loop, publishing scheduled notes every minute. Failures are
+    /// logged by [`NoteService::publish_scheduled`] and retried on the next tick.
+    pub async fn continuously_publish_scheduled(self) -> Result<(), tokio_rusqlite::Error> {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            let _ = self.publish_scheduled().await;
+        }
+    }
+
+    /// Publish all scheduled notes whose publication time has passed. Each note's creation time is
+    /// set to its scheduled publication time.
+    #[tracing::instrument(skip(self), ret, err)]
+    pub async fn publish_scheduled(&self) -> Result<usize, tokio_rusqlite::Error> {
+        Ok(self
+            .db
+            .call_unwrap(|conn| {
+                conn.prepare_cached(
+                    r#"
+                    update note
+                    set status = ?, created_at = publish_at, updated_at = null
+                    where status = ? and publish_at <= current_timestamp
+                    "#,
+                )?
+                .execute(params![NoteStatus::Published, NoteStatus::Scheduled])
+            })
+            .await?)
+    }
+
+    /// Replace the body of an existing [`Note`], recording its previous body as a [`Revision`].
+    /// Does nothing if the body is unchanged. Returns `false` if no such note exists or if it has
+    /// been deleted.
+    #[tracing::instrument(skip(self, body), ret, err)]
+    pub async fn update(&self, note_id: &str, body: String) -> Result<bool, tokio_rusqlite::Error> {
+        let note_id = note_id.to_string();
+        Ok(self
+            .db
+            .call_unwrap(move |conn| -> Result<bool, rusqlite::Error> {
+                let tx = conn.transaction()?;
+                let old_body = tx
+                    .prepare_cached(
+                        r#"
+                        select body from note
+                        where note_id = ? and status in (?, ?, ?)
+                        "#,
+                    )?
+                    .query_row(
+                        params![
+                            note_id,
+                            NoteStatus::Published,
+                            NoteStatus::Draft,
+                            NoteStatus::Scheduled
+                        ],
+                        |row| row.get::<_, String>(0),
+                    )
+                    .optional()?;
+                match old_body {
+                    None => return Ok(false),
+                    Some(old_body) if old_body == body => return Ok(true),
+                    Some(_) => {}
+                }
+                tx.prepare_cached(
+                    r#"
+                    insert into note_revision (note_id, body)
+                    select note_id, body from note where note_id = ?
+                    "#,
+                )?
+                .execute(params![note_id])?;
+                let updated = tx
+                    .prepare_cached(
+                        r#"
+                        update note
+                        set body = ?, updated_at = current_timestamp
+                        where note_id = ?
+                        "#,
+                    )?
+                    .execute(params![body, note_id])?;
+                if updated > 0 {
+                    index_tags(&tx, &note_id, &body)?;
+                }
+                tx.commit()?;
+                Ok(updated > 0)
+            })
+            .await?)
+    }
+
+    /// Return all previous [`Revision`]s of the given note in reverse chronological order.
+    #[tracing::instrument(skip(self), err)]
+    pub async fn revisions(&self, note_id: &str) -> Result<Vec<Revision>, tokio_rusqlite::Error> {
+        let note_id = note_id.to_string();
+        Ok(self
+            .db
+            .call_unwrap(move |conn| {
+                conn.prepare_cached(
+                    r#"
+                    select body, created_at
+                    from note_revision
+                    where note_id = ?
+                    order by created_at desc, rowid desc
+                    "#,
+                )?
+                .query_map(params![note_id], |row| {
+                    Ok(Revision { body: row.get(0)?, created_at: row.get(1)? })
+                })?
+                .collect::<Result<Vec<_>, _>>()
+            })
+            .await?)
+    }
+
+    /// Mark a [`Note`] as deleted, removing it from all public views. Returns `false` if no such
+    /// note exists.
+    #[tracing::instrument(skip(self), ret, err)]
+    pub async fn delete(&self, note_id: &str) -> Result<bool, tokio_rusqlite::Error> {
+        let note_id = note_id.to_string();
+        Ok(self
+            .db
+            .call_unwrap(move |conn| {
+                conn.prepare_cached(
+                    r#"
+                    update note
+                    set status = ?, updated_at = current_timestamp
+                    where note_id = ?
+                    "#,
+                )?
+                .execute(params![NoteStatus::Deleted, note_id])
+            })
+            .await?
+            > 0)
+    }
+
+    /// Permanently remove a [`Note`] and all of its revisions. Returns `false` if no such note
+    /// exists.
+    #[tracing::instrument(skip(self), ret, err)]
+    pub async fn purge(&self, note_id: &str) -> Result<bool, tokio_rusqlite::Error> {
+        let note_id = note_id.to_string();
+        Ok(self
+            .db
+            .call_unwrap(move |conn| -> Result<bool, rusqlite::Error> {
+                let tx = conn.transaction()?;
+                tx.prepare_cached(r#"delete from note_revision where note_id = ?"#)?
+                    .execute(params![note_id])?;
+                tx.prepare_cached(r#"delete from note_tag where note_id = ?"#)?
+                    .execute(params![note_id])?;
+                let purged = tx
+                    .prepare_cached(r#"delete from note where note_id = ?"#)?
+                    .execute(params![note_id])?;
+                tx.commit()?;
+                Ok(purged > 0)
+            })
+            .await?)
+    }
+
     /// Find a [`Note`] by ID.
     #[tracing::instrument(skip(self), err)]
     pub async fn by_id(&self, note_id: &str) -> Result<Option<Note>, tokio_rusqlite::Error> {
@@ -42,7 +339,7 @@ impl NoteService {
             .call_unwrap(move |conn| {
                 conn.prepare_cached(
                     r#"
-                    select note_id, body, created_at
+                    select note_id, body, created_at, updated_at, status, publish_at
                     from note
                     where note_id = ?
                     "#,
@@ -61,65 +358,376 @@ impl NoteService {
             .call_unwrap(move |conn| {
                 conn.prepare_cached(
                     r#"
-                    select note_id, body, created_at
+                    select note_id, body, created_at, updated_at, status, publish_at
                     from note
+                    where status = ?
                     order by created_at desc
                     limit ?
                     "#,
                 )?
-                .query_map(params![n], |row| row.try_into())?
+                .query_map(params![NoteStatus::Published, n], |row| row.try_into())?
                 .collect::<Result<Vec<_>, _>>()
             })
             .await?)
     }
 
-    /// Return a vec of all week-long date ranges in which notes were created.
+    /// Find up to `n` published [`Note`]s created before the given cursor (or the most recent, if
+    /// no cursor is given) in reverse chronological order.
     #[tracing::instrument(skip(self), err)]
-    pub async fn weeks(&self) -> Result<Vec<Range<Date>>, tokio_rusqlite::Error> {
+    pub async fn before(
+        &self,
+        cursor: Option<Cursor>,
+        n: u16,
+    ) -> Result<Vec<Note>, tokio_rusqlite::Error> {
+        Ok(self
+            .db
+            .call_unwrap(move |conn| {
+                let (created_at, note_id) = match cursor {
+                    Some(Cursor(created_at, note_id)) => (Some(created_at), Some(note_id)),
+                    None => (None, None),
+                };
+                conn.prepare_cached(
+                    r#"
+                    select note_id, body, created_at, updated_at, status, publish_at
+                    from note
+                    where status = ? and (?2 is null or (created_at, note_id) < (datetime(?2), ?3))
+                    order by created_at desc, note_id desc
+                    limit ?4
+                    "#,
+                )?
+                .query_map(params![NoteStatus::Published, created_at, note_id, n], |row| {
+                    row.try_into()
+                })?
+                .collect::<Result<Vec<_>, _>>()
+            })
+            .await?)
+    }
+
+    /// Find up to `n` published [`Note`]s created after the given cursor in reverse chronological
+    /// order.
+    #[tracing::instrument(skip(self), err)]
+    pub async fn after(&self, cursor: Cursor, n: u16) -> Result<Vec<Note>, tokio_rusqlite::Error> {
+        let Cursor(created_at, note_id) = cursor;
+        let mut notes = self
+            .db
+            .call_unwrap(move |conn| {
+                conn.prepare_cached(
+                    r#"
+                    select note_id, body, created_at, updated_at, status, publish_at
+                    from note
+                    where status = ? and (created_at, note_id) > (datetime(?), ?)
+                    order by created_at, note_id
+                    limit ?
+                    "#,
+                )?
+                .query_map(params![NoteStatus::Published, created_at, note_id, n], |row| {
+                    row.try_into()
+                })?
+                .collect::<Result<Vec<_>, _>>()
+            })
+            .await?;
+        notes.reverse();
+        Ok(notes)
+    }
+
+    /// Find the `n` most recent [`Note`]s with the given status in reverse chronological order.
+    #[tracing::instrument(skip(self), err)]
+    pub async fn by_status(
+        &self,
+        status: NoteStatus,
+        n: u16,
+    ) -> Result<Vec<Note>, tokio_rusqlite::Error> {
+        Ok(self
+            .db
+            .call_unwrap(move |conn| {
+                conn.prepare_cached(
+                    r#"
+                    select note_id, body, created_at, updated_at, status, publish_at
+                    from note
+                    where status = ?
+                    order by coalesce(updated_at, created_at) desc
+                    limit ?
+                    "#,
+                )?
+                .query_map(params![status, n], |row| row.try_into())?
+                .collect::<Result<Vec<_>, _>>()
+            })
+            .await?)
+    }
+
+    /// Find the `n` most recent [`Note`]s with the given tag in reverse chronological order.
+    #[tracing::instrument(skip(self), err)]
+    pub async fn tagged(&self, tag: &str, n: u16) -> Result<Vec<Note>, tokio_rusqlite::Error> {
+        let tag = tag.to_lowercase();
+        Ok(self
+            .db
+            .call_unwrap(move |conn| {
+                conn.prepare_cached(
+                    r#"
+                    select note_id, body, created_at, updated_at, status, publish_at
+                    from note
+                    where status = ? and note_id in (select note_id from note_tag where tag = ?)
+                    order by created_at desc
+                    limit ?
+                    "#,
+                )?
+                .query_map(params![NoteStatus::Published, tag, n], |row| row.try_into())?
+                .collect::<Result<Vec<_>, _>>()
+            })
+            .await?)
+    }
+
+    /// Find all [`Note`]s, of any status, whose bodies contain the given text in reverse
+    /// chronological order.
+    #[tracing::instrument(skip(self), err)]
+    pub async fn containing(&self, text: &str) -> Result<Vec<Note>, tokio_rusqlite::Error> {
+        let text = text.to_string();
+        Ok(self
+            .db
+            .call_unwrap(move |conn| {
+                conn.prepare_cached(
+                    r#"
+                    select note_id, body, created_at, updated_at, status, publish_at
+                    from note
+                    where instr(body, ?) > 0
+                    order by created_at desc
+                    "#,
+                )?
+                .query_map(params![text], |row| row.try_into())?
+                .collect::<Result<Vec<_>, _>>()
+            })
+            .await?)
+    }
+
+    /// Returns the images embedded in every [`Note`], of any status, and in every [`Revision`].
+    #[tracing::instrument(skip(self), err)]
+    pub async fn all_images(
+        &self,
+        base_url: &Url,
+    ) -> Result<Vec<NoteImage>, tokio_rusqlite::Error> {
+        let bodies = self
+            .db
+            .call_unwrap(move |conn| {
+                conn.prepare_cached(
+                    r#"
+                    select body from note
+                    union all
+                    select body from note_revision
+                    "#,
+                )?
+                .query_map([], |row| row.get::<_, String>(0))?
+                .collect::<Result<Vec<_>, _>>()
+            })
+            .await?;
+        Ok(bodies.iter().flat_map(|body| body_images(body, base_url)).collect())
+    }
+
+    /// Return all tags used in published notes, most frequently used first.
+    #[tracing::instrument(skip(self), err)]
+    pub async fn tags(&self) -> Result<Vec<NoteTag>, tokio_rusqlite::Error> {
+        Ok(self
+            .db
+            .call_unwrap(move |conn| {
+                conn.prepare_cached(
+                    r#"
+                    select tag, count(note_id)
+                    from note_tag join note using (note_id)
+                    where status = ?
+                    group by tag
+                    order by 2 desc, 1
+                    "#,
+                )?
+                .query_map(params![NoteStatus::Published], |row| {
+                    Ok(NoteTag { tag: row.get(0)?, count: row.get(1)? })
+                })?
+                .collect::<Result<Vec<_>, _>>()
+            })
+            .await?)
+    }
+
+    /// Find the `n` published [`Note`]s which best match the given full-text search query.
+    #[tracing::instrument(skip(self), err)]
+    pub async fn search(
+        &self,
+        query: &str,
+        n: u16,
+    ) -> Result<Vec<SearchResult>, tokio_rusqlite::Error> {
+        // Quote each term so that user input is never interpreted as FTS5 query syntax.
+        let query = query
+            .split_whitespace()
+            .map(|term| format!(r#""{}""#, term.replace('"', r#""""#)))
+            .collect::<Vec<_>>()
+            .join(" ");
+        if query.is_empty() {
+            return Ok(vec![]);
+        }
+
         Ok(self
             .db
             .call_unwrap(move |conn| {
                 conn.prepare_cached(
                     r#"
                     select
-                        date(local, 'weekday 0', '-7 days'),
-                        date(local, 'weekday 0')
-                    from (select datetime(created_at, 'localtime') as local from note)
-                    group by 1 order by 1 desc
+                        note.note_id,
+                        note.body,
+                        note.created_at,
+                        note.updated_at,
+                        note.status,
+                        note.publish_at,
+                        snippet(note_fts, 1, char(2), char(3), '…', 32)
+                    from note_fts join note on note.note_id = note_fts.note_id
+                    where note_fts match ? and note.status = ?
+                    order by rank
+                    limit ?
                     "#,
                 )?
-                .query_map([], |row| {
-                    let start = row.get::<_, Date>(0)?;
-                    let end = row.get::<_, Date>(1)?;
-                    Ok(start..end)
+                .query_map(params![query, NoteStatus::Published, n], |row| {
+                    Ok(SearchResult {
+                        note: row.try_into()?,
+                        snippet: highlight_snippet(&row.get::<_, String>(6)?),
+                    })
                 })?
                 .collect::<Result<Vec<_>, _>>()
             })
             .await?)
     }
 
-    /// Return all [`Note`]s which were created in the given date range.
+    /// Parse the tags of every note, once, to index notes written before tags were supported.
+    /// Returns `false` if the notes have already been indexed.
+    #[tracing::instrument(skip(self), ret, err)]
+    pub async fn backfill_tags(&self) -> Result<bool, tokio_rusqlite::Error> {
+        Ok(self
+            .db
+            .call_unwrap(move |conn| -> Result<bool, rusqlite::Error> {
+                let tx = conn.transaction()?;
+                let pending = tx
+                    .prepare_cached(r#"delete from backfill where name = 'note_tag'"#)?
+                    .execute([])?;
+                if pending == 0 {
+                    return Ok(false);
+                }
+                let notes = tx
+                    .prepare_cached(r#"select note_id, body from note"#)?
+                    .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+                    .collect::<Result<Vec<_>, _>>()?;
+                for (note_id, body) in notes {
+                    index_tags(&tx, &note_id, &body)?;
+                }
+                tx.commit()?;
+                Ok(true)
+            })
+            .await?)
+    }
+
+    /// Return a vec of all week-long date ranges in which notes were created, starting on Sundays
+    /// in the configured time zone.
+    #[tracing::instrument(skip(self), err)]
+    pub async fn weeks(&self) -> Result<Vec<Range<Date>>, tokio_rusqlite::Error> {
+        let starts = self.local_dates().await?.into_keys().map(|d| week_of(d).start);
+        Ok(starts.collect::<BTreeSet<_>>().into_iter().rev().map(week_of).collect())
+    }
+
+    /// Return a summary of the public state of all notes, which changes whenever a note is
+    /// published, edited, deleted, or purged.
+    #[tracing::instrument(skip(self), ret, err)]
+    pub async fn version(&self) -> Result<NotesVersion, tokio_rusqlite::Error> {
+        Ok(self
+            .db
+            .call_unwrap(move |conn| {
+                conn.prepare_cached(
+                    r#"
+                    select
+                      (select max(modified_at) from note where status in (?, ?)),
+                      (select count(*) from note where status = ?),
+                      (select count(*) from note_revision)
+                    "#,
+                )?
+                .query_row(
+                    params![NoteStatus::Published, NoteStatus::Deleted, NoteStatus::Published],
+                    |row| {
+                        Ok(NotesVersion {
+                            modified_at: row.get(0)?,
+                            notes: row.get(1)?,
+                            revisions: row.get(2)?,
+                        })
+                    },
+                )
+            })
+            .await?)
+    }
+
+    /// Return the week, starting on Sunday, which contains the current local date.
+    pub fn current_week(&self) -> Result<Range<Date>, tokio_rusqlite::Error> {
+        let now = to_local(&self.time_zone, OffsetDateTime::now_utc()).map_err(other)?;
+        Ok(week_of(now.date()))
+    }
+
+    /// Return the first day of each month in which notes were published, along with the number of
+    /// notes published in that month.
+    #[tracing::instrument(skip(self), err)]
+    pub async fn months(&self) -> Result<Vec<NoteMonth>, tokio_rusqlite::Error> {
+        let mut months = BTreeMap::<Date, u32>::new();
+        for (date, count) in self.local_dates().await? {
+            *months.entry(date.replace_day(1).expect("should be a valid date")).or_default() +=
+                count;
+        }
+        Ok(months.into_iter().rev().map(|(start, count)| NoteMonth { start, count }).collect())
+    }
+
+    /// Return all [`Note`]s which were created in the given date range, with days starting at
+    /// midnight in the configured time zone.
     #[tracing::instrument(skip(self), err)]
     pub async fn date_range(&self, range: Range<Date>) -> Result<Vec<Note>, tokio_rusqlite::Error> {
-        let start = OffsetDateTime::new_utc(range.start, Time::MIDNIGHT);
-        let end = OffsetDateTime::new_utc(range.end, Time::MIDNIGHT);
+        let start = start_of_day(&self.time_zone, range.start).map_err(other)?;
+        let end = start_of_day(&self.time_zone, range.end).map_err(other)?;
 
         Ok(self
             .db
             .call_unwrap(move |conn| {
                 conn.prepare_cached(
                     r#"
-                    select note_id, body, created_at
+                    select note_id, body, created_at, updated_at, status, publish_at
                     from note
-                    where created_at >= ? and created_at < ?
+                    where status = ? and created_at >= datetime(?) and created_at < datetime(?)
                     order by created_at desc
                     "#,
                 )?
-                .query_map(params![start, end], |row| row.try_into())?
+                .query_map(params![NoteStatus::Published, start, end], |row| row.try_into())?
                 .collect::<Result<Vec<_>, _>>()
             })
             .await?)
     }
+
+    /// Return the local dates on which published notes were created, with the number of notes
+    /// created on each.
+    async fn local_dates(&self) -> Result<BTreeMap<Date, u32>, tokio_rusqlite::Error> {
+        // Count notes in quarter-hour buckets of UTC time. Modern UTC offsets and DST transitions
+        // all fall on quarter hours, so every note in a bucket has the same local date.
+        let buckets = self
+            .db
+            .call_unwrap(move |conn| {
+                conn.prepare_cached(
+                    r#"
+                    select unixepoch(created_at) / 900 * 900 as bucket, count(*)
+                    from note
+                    where status = ?
+                    group by bucket
+                    "#,
+                )?
+                .query_map(params![NoteStatus::Published], |row| {
+                    Ok((row.get::<_, i64>(0)?, row.get::<_, u32>(1)?))
+                })?
+                .collect::<Result<Vec<_>, _>>()
+            })
+            .await?;
+        let mut dates = BTreeMap::new();
+        for (bucket, count) in buckets {
+            let t = OffsetDateTime::from_unix_timestamp(bucket).map_err(|e| other(e.into()))?;
+            let date = to_local(&self.time_zone, t).map_err(other)?.date();
+            *dates.entry(date).or_default() += count;
+        }
+        Ok(dates)
+    }
 }
 
 /// A shitpost with a Markdown body.
@@ -131,32 +739,51 @@ pub struct Note {
     pub body: String,
     /// The date and time at which the note was created.
     pub created_at: OffsetDateTime,
+    /// The date and time at which the note was last edited, if ever.
+    pub updated_at: Option<OffsetDateTime>,
+    /// The note's publication status.
+    pub status: NoteStatus,
+    /// The date and time at which a scheduled note will be published.
+    pub publish_at: Option<OffsetDateTime>,
 }
 
 impl Note {
-    /// Returns the note's body as HTML.
-    pub fn to_html(&self) -> String {
+    /// Returns a cursor which identifies the note's position in the feed.
+    pub fn cursor(&self) -> Cursor {
+        Cursor(self.created_at, self.note_id)
+    }
+
+    /// Returns the date and time at which the note was last modified.
+    pub fn modified_at(&self) -> OffsetDateTime {
+        self.updated_at.unwrap_or(self.created_at)
+    }
+
+    /// Returns the note's body as HTML. Local images with responsive variants are rendered with
+    /// `srcset`, `sizes`, `width`, and `height` attributes.
+    pub fn to_html(&self, images: &ResponsiveImages) -> String {
         let mut out = String::with_capacity(256);
-        pulldown_cmark::html::push_html(&mut out, parse_md(&self.body));
+        pulldown_cmark::html::push_html(
+            &mut out,
+            responsive_images(link_tags(parse_md(&self.body)), images),
+        );
         out
     }
 
-    /// Return a vec of the URLs of all images in the note.
-    pub fn images(&self, base_url: &Url) -> Vec<Url> {
+    /// Returns the sources of all images in the note, as written.
+    pub fn image_srcs(&self) -> Vec<String> {
         parse_md(&self.body)
-            .flat_map(|e| match e {
-                Event::Start(Tag::Image { dest_url, .. }) => {
-                    if dest_url.starts_with("http://") || dest_url.starts_with("https://") {
-                        dest_url.parse().ok()
-                    } else {
-                        base_url.join(dest_url.as_ref()).ok()
-                    }
-                }
+            .filter_map(|e| match e {
+                Event::Start(Tag::Image { dest_url, .. }) => Some(dest_url.to_string()),
                 _ => None,
             })
             .collect()
     }
 
+    /// Return a vec of all images in the note.
+    pub fn images(&self, base_url: &Url) -> Vec<NoteImage> {
+        body_images(&self.body, base_url)
+    }
+
     /// Returns a plain-text version of the note.
     pub fn description(&self) -> String {
         let mut out = String::with_capacity(256);
@@ -174,22 +801,503 @@ impl Note {
         }
         out.trim().into()
     }
+
+    /// Returns a plain-text title for the note: the text of its first heading, if any, or else the
+    /// beginning of its text, either truncated on a word boundary. Notes with only images, video, or audio
+    /// are titled with the first alt text, or failing that, the date they were created in the given
+    /// time zone.
+    pub fn title(&self, time_zone: &TimeZone) -> String {
+        let (mut heading, mut alt, mut first_alt) = (None::<String>, None::<String>, None);
+        let mut text = String::with_capacity(256);
+        for e in parse_md(&self.body) {
+            match e {
+                Event::Start(Tag::Image { .. }) => alt = Some(String::new()),
+                Event::End(TagEnd::Image) => {
+                    let alt = alt.take().unwrap_or_default();
+                    if first_alt.is_none() && !alt.trim().is_empty() {
+                        first_alt = Some(alt);
+                    }
+                }
+                Event::Text(t) | Event::Code(t) => match (alt.as_mut(), heading.as_mut()) {
+                    (Some(alt), _) => alt.push_str(&t),
+                    (None, Some(heading)) => heading.push_str(&t),
+                    (None, None) => text.push_str(&t),
+                },
+                Event::Start(Tag::Heading { .. }) => heading = Some(String::new()),
+                Event::End(TagEnd::Heading(_)) => match heading.take() {
+                    Some(heading) if !heading.trim().is_empty() => {
+                        return truncate_words(heading.trim(), TITLE_LEN);
+                    }
+                    _ => {}
+                },
+                Event::SoftBreak
+                | Event::HardBreak
+                | Event::Start(Tag::Paragraph)
+                | Event::Rule => text.push(' '),
+                _ => {}
+            }
+        }
+
+        match [Some(text), first_alt]
+            .into_iter()
+            .flatten()
+            .map(|t| t.split_whitespace().collect::<Vec<_>>().join(" "))
+            .find(|t| !t.is_empty())
+        {
+            Some(title) => truncate_words(&title, TITLE_LEN),
+            None => {
+                let created_at = to_local(time_zone, self.created_at).unwrap_or(self.created_at);
+                format!("{} {}, {}", created_at.month(), created_at.day(), created_at.year())
+            }
+        }
+    }
+
+    /// Returns the unique, lowercase hashtags in the note.
+    pub fn tags(&self) -> Vec<String> {
+        parse_tags(&self.body)
+    }
+}
+
+/// The maximum length of a title derived from a note's description, in characters.
+const TITLE_LEN: usize = 80;
+
+/// Truncates the given text to at most `max` characters, breaking on a word boundary and marking
+/// the omission with an ellipsis.
+fn truncate_words(text: &str, max: usize) -> String {
+    let Some((end, next)) = text.char_indices().nth(max) else {
+        return text.into();
+    };
+    let head = &text[..end];
+    let head = match head.rfind(char::is_whitespace) {
+        Some(i) if !next.is_whitespace() && i > 0 => &head[..i],
+        _ => head,
+    };
+    format!("{}…", head.trim_end_matches(|c: char| c.is_whitespace() || c.is_ascii_punctuation()))
 }
 
 impl<'stmt> TryFrom<&'stmt Row<'stmt>> for Note {
     type Error = rusqlite::Error;
 
     fn try_from(row: &'stmt Row<'stmt>) -> Result<Self, Self::Error> {
-        Ok(Note { note_id: row.get(0)?, body: row.get(1)?, created_at: row.get(2)? })
+        Ok(Note {
+            note_id: row.get(0)?,
+            body: row.get(1)?,
+            created_at: row.get(2)?,
+            updated_at: row.get(3)?,
+            status: row.get(4)?,
+            publish_at: row.get(5)?,
+        })
+    }
+}
+
+/// An image embedded in a [`Note`].
+#[derive(Debug)]
+pub struct NoteImage {
+    /// The absolute URL of the image.
+    pub url: Url,
+    /// The image's alt text.
+    pub alt: String,
+}
+
+/// The publication status of a [`Note`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoteStatus {
+    /// The note is visible to the public.
+    Published,
+    /// The note is still being written and is only visible to the author.
+    Draft,
+    /// The note will be published at a later time.
+    Scheduled,
+    /// The note has been deleted and is only visible as a tombstone.
+    Deleted,
+}
+
+impl NoteStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            NoteStatus::Published => "published",
+            NoteStatus::Draft => "draft",
+            NoteStatus::Scheduled => "scheduled",
+            NoteStatus::Deleted => "deleted",
+        }
+    }
+}
+
+impl FromSql for NoteStatus {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value.as_str()? {
+            "published" => Ok(NoteStatus::Published),
+            "draft" => Ok(NoteStatus::Draft),
+            "scheduled" => Ok(NoteStatus::Scheduled),
+            "deleted" => Ok(NoteStatus::Deleted),
+            s => Err(FromSqlError::Other(format!("invalid note status: {s}").into())),
+        }
+    }
+}
+
+impl ToSql for NoteStatus {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+/// A position in the reverse chronological feed of notes, used for pagination.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cursor(OffsetDateTime, PublicId);
+
+impl Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}_{}", self.0.unix_timestamp(), self.1)
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (timestamp, note_id) =
+            s.split_once('_').ok_or_else(|| anyhow::anyhow!("invalid cursor: {s}"))?;
+        Ok(Cursor(OffsetDateTime::from_unix_timestamp(timestamp.parse()?)?, note_id.parse()?))
+    }
+}
+
+/// A hashtag and the number of published notes which use it.
+#[derive(Debug)]
+pub struct NoteTag {
+    /// The tag, normalized to lowercase and without a leading `#`.
+    pub tag: String,
+    /// The number of published notes with the tag.
+    pub count: u32,
+}
+
+/// A month in which notes were published.
+#[derive(Debug)]
+pub struct NoteMonth {
+    /// The first day of the month.
+    pub start: Date,
+    /// The number of published notes in the month.
+    pub count: u32,
+}
+
+impl NoteMonth {
+    /// The first day of the following month, or the last representable date if there isn't one.
+    pub fn end(&self) -> Date {
+        next_month(self.start).unwrap_or(Date::MAX)
+    }
+}
+
+/// Returns the first day of the month after the given date's month, if it's representable.
+pub fn next_month(date: Date) -> Option<Date> {
+    let (year, month) = match date.month() {
+        Month::December => (date.year() + 1, Month::January),
+        month => (date.year(), month.next()),
+    };
+    Date::from_calendar_date(year, month, 1).ok()
+}
+
+/// Returns the given time in the given time zone, using the zone's UTC offset at that instant.
+pub fn to_local(time_zone: &TimeZone, t: OffsetDateTime) -> anyhow::Result<OffsetDateTime> {
+    let offset = time_zone.find_local_time_type(t.unix_timestamp())?.ut_offset();
+    Ok(t.to_offset(UtcOffset::from_whole_seconds(offset)?))
+}
+
+/// Returns the first instant of the given date in the given time zone. If midnight was skipped by a
+/// DST transition, this is the instant of the transition.
+pub fn start_of_day(time_zone: &TimeZone, date: Date) -> anyhow::Result<OffsetDateTime> {
+    let local = tz::DateTime::find(
+        date.year(),
+        date.month().into(),
+        date.day(),
+        0,
+        0,
+        0,
+        0,
+        time_zone.as_ref(),
+    )?
+    .earliest()
+    .ok_or_else(|| anyhow::anyhow!("no local time for {date}"))?;
+    Ok(OffsetDateTime::from_unix_timestamp(local.unix_time())?)
+}
+
+/// Returns the week, starting on Sunday, which contains the given date.
+fn week_of(date: Date) -> Range<Date> {
+    let start = date - time::Duration::days(date.weekday().number_days_from_sunday().into());
+    start..start + time::Duration::days(7)
+}
+
+fn other(e: anyhow::Error) -> tokio_rusqlite::Error {
+    tokio_rusqlite::Error::Other(e.into())
+}
+
+/// A summary of the public state of all notes. See [`NoteService::version`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NotesVersion {
+    /// When a published or deleted note was most recently published, edited, or deleted, if ever.
+    pub modified_at: Option<OffsetDateTime>,
+    /// The number of published notes.
+    pub notes: u64,
+    /// The number of previous revisions of all notes.
+    pub revisions: u64,
+}
+
+/// A [`Note`] which matched a search query.
+#[derive(Debug)]
+pub struct SearchResult {
+    /// The matching note.
+    pub note: Note,
+    /// An HTML excerpt of the note's body with the matching terms wrapped in `<mark>` elements.
+    pub snippet: String,
+}
+
+/// A previous version of a [`Note`]'s body.
+#[derive(Debug)]
+pub struct Revision {
+    /// The note's Markdown body before it was edited.
+    pub body: String,
+    /// The date and time at which the note was edited.
+    pub created_at: OffsetDateTime,
+}
+
+/// Returns all images in the given Markdown, resolving relative URLs against the base URL.
+fn body_images(body: &str, base_url: &Url) -> Vec<NoteImage> {
+    let mut images = Vec::new();
+    let mut alt = None;
+    for e in parse_md(body) {
+        match e {
+            Event::Start(Tag::Image { dest_url, .. }) => {
+                let url = if dest_url.starts_with("http://") || dest_url.starts_with("https://") {
+                    dest_url.parse().ok()
+                } else {
+                    base_url.join(dest_url.as_ref()).ok()
+                };
+                alt = url.map(|url| NoteImage { url, alt: String::new() });
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some(image) = &mut alt {
+                    image.alt.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::Image) => images.extend(alt.take()),
+            _ => {}
+        }
+    }
+    images
+}
+
+fn parse_md(md: &str) -> TextMergeStream<'_, Parser<'_>> {
+    TextMergeStream::new(Parser::new_ext(
+        md,
+        Options::ENABLE_SMART_PUNCTUATION | Options::ENABLE_STRIKETHROUGH,
+    ))
+}
+
+/// Converts an FTS5 snippet delimited with `\x02` and `\x03` into escaped HTML with `<mark>`
+/// elements.
+fn highlight_snippet(snippet: &str) -> String {
+    let mut events = Vec::new();
+    for (i, part) in snippet.split('\x02').enumerate() {
+        match part.split_once('\x03') {
+            Some((highlighted, rest)) if i > 0 => {
+                events.push(Event::InlineHtml("<mark>".into()));
+                events.push(Event::Text(highlighted.into()));
+                events.push(Event::InlineHtml("</mark>".into()));
+                events.push(Event::Text(rest.into()));
+            }
+            _ => events.push(Event::Text(part.into())),
+        }
+    }
+    let mut out = String::with_capacity(snippet.len() + 32);
+    pulldown_cmark::html::push_html(&mut out, events.into_iter());
+    out
+}
+
+/// Replaces the tags of the given note with those parsed from its body.
+fn index_tags(
+    conn: &rusqlite::Connection,
+    note_id: &dyn ToSql,
+    body: &str,
+) -> rusqlite::Result<()> {
+    conn.prepare_cached(r#"delete from note_tag where note_id = ?"#)?.execute(params![note_id])?;
+    let mut insert = conn.prepare_cached(r#"insert into note_tag (note_id, tag) values (?, ?)"#)?;
+    for tag in parse_tags(body) {
+        insert.execute(params![note_id, tag])?;
     }
+    Ok(())
+}
+
+/// Returns the unique, lowercase hashtags in the given Markdown document.
+fn parse_tags(md: &str) -> Vec<String> {
+    let mut tags = Vec::new();
+    for (taggable, e) in taggable(parse_md(md)) {
+        if let (true, Event::Text(text)) = (taggable, e) {
+            for range in find_tags(&text) {
+                let tag = text[range.start + 1..range.end].to_lowercase();
+                if !tags.contains(&tag) {
+                    tags.push(tag);
+                }
+            }
+        }
+    }
+    tags
+}
+
+/// Replaces hashtags in the given Markdown events with links to the tags' pages.
+fn link_tags<'a>(events: impl Iterator<Item = Event<'a>>) -> impl Iterator<Item = Event<'a>> {
+    taggable(events).flat_map(|(taggable, e)| match e {
+        Event::Text(text) if taggable => {
+            let mut out = Vec::with_capacity(1);
+            let mut last = 0;
+            for range in find_tags(&text) {
+                if range.start > last {
+                    out.push(Event::Text(text[last..range.start].to_string().into()));
+                }
+                out.push(Event::Start(Tag::Link {
+                    link_type: LinkType::Inline,
+                    dest_url: format!("/tags/{}", text[range.start + 1..range.end].to_lowercase())
+                        .into(),
+                    title: CowStr::Borrowed(""),
+                    id: CowStr::Borrowed(""),
+                }));
+                out.push(Event::Text(text[range.clone()].to_string().into()));
+                out.push(Event::End(TagEnd::Link));
+                last = range.end;
+            }
+            if last == 0 {
+                out.push(Event::Text(text));
+            } else if last < text.len() {
+                out.push(Event::Text(text[last..].to_string().into()));
+            }
+            out
+        }
+        e => vec![e],
+    })
+}
+
+/// Replaces images which have responsive variants with `<img>` elements which list them, poster
+/// frames of videos with `<video>` elements, and audio with `<audio>` elements.
+fn responsive_images<'a>(
+    events: impl Iterator<Item = Event<'a>>,
+    images: &ResponsiveImages,
+) -> impl Iterator<Item = Event<'a>> {
+    let mut current: Option<(String, Option<&ResponsiveImage>, CowStr<'a>, String)> = None;
+    events.filter_map(move |e| match (e, &mut current) {
+        (Event::Start(Tag::Image { dest_url, title, .. }), None)
+            if images.get(&dest_url).is_some() || images.audio(&dest_url).is_some() =>
+        {
+            current = Some((dest_url.to_string(), images.get(&dest_url), title, String::new()));
+            None
+        }
+        (Event::Text(text) | Event::Code(text), Some((_, _, _, alt))) => {
+            alt.push_str(&text);
+            None
+        }
+        (Event::End(TagEnd::Image), Some(_)) => {
+            let (src, image, title, alt) = current.take().expect("should have an image");
+            let Some(image) = image else {
+                let mut html = format!(
+                    r#"<audio controls preload="metadata" src="{}" aria-label="{}""#,
+                    escape_attr(&src),
+                    escape_attr(&alt),
+                );
+                if !title.is_empty() {
+                    html.push_str(&format!(r#" title="{}""#, escape_attr(&title)));
+                }
+                html.push_str(&format!(
+                    r#"><a href="{}">{}</a></audio>"#,
+                    escape_attr(&src),
+                    escape_attr(if alt.is_empty() { "Audio" } else { &alt }),
+                ));
+                return Some(Event::InlineHtml(html.into()));
+            };
+            // Main images are 600px wide, so render them at that size.
+            let height = u64::from(image.height) * 600 / u64::from(image.width.max(1));
+            if let Some(video) = &image.video {
+                let mut html = format!(
+                    r#"<video controls playsinline preload="metadata" poster="{}" width="600" height="{height}" aria-label="{}""#,
+                    escape_attr(&src),
+                    escape_attr(&alt),
+                );
+                if !title.is_empty() {
+                    html.push_str(&format!(r#" title="{}""#, escape_attr(&title)));
+                }
+                html.push_str(&format!(
+                    r#"><source src="{}" type="{}" /><a href="{}">{}</a></video>"#,
+                    escape_attr(&video.src),
+                    escape_attr(&video.content_type),
+                    escape_attr(&video.src),
+                    escape_attr(if alt.is_empty() { "Video" } else { &alt }),
+                ));
+                return Some(Event::InlineHtml(html.into()));
+            }
+            let mut html = format!(
+                r#"<img src="{}" srcset="{}" sizes="(max-width: 600px) 100vw, 600px" width="600" height="{height}" alt="{}""#,
+                escape_attr(&src),
+                escape_attr(&image.srcset),
+                escape_attr(&alt),
+            );
+            if !title.is_empty() {
+                html.push_str(&format!(r#" title="{}""#, escape_attr(&title)));
+            }
+            html.push_str(" />");
+            Some(Event::InlineHtml(html.into()))
+        }
+        (_, Some(_)) => None,
+        (e, None) => Some(e),
+    })
+}
+
+/// Escapes the given string for use in a double-quoted HTML attribute.
+fn escape_attr(s: &str) -> String {
+    s.replace('&', "&amp;").replace('"', "&quot;").replace('<', "&lt;").replace('>', "&gt;")
 }
 
-fn parse_md(md: &str) -> Parser<'_> {
-    Parser::new_ext(md, Options::ENABLE_SMART_PUNCTUATION | Options::ENABLE_STRIKETHROUGH)
+/// Pairs each event with whether or not it may contain hashtags. Text inside links, images, and
+/// code blocks is not tagged.
+fn taggable<'a>(
+    events: impl Iterator<Item = Event<'a>>,
+) -> impl Iterator<Item = (bool, Event<'a>)> {
+    events.scan(0usize, |depth, e| {
+        match &e {
+            Event::Start(Tag::Link { .. } | Tag::Image { .. } | Tag::CodeBlock(_)) => *depth += 1,
+            Event::End(TagEnd::Link | TagEnd::Image | TagEnd::CodeBlock) => *depth -= 1,
+            _ => {}
+        }
+        Some((*depth == 0, e))
+    })
+}
+
+/// Returns the byte ranges of all hashtags (including the `#`) in the given text. A hashtag is a
+/// `#` which doesn't follow an alphanumeric character, followed by alphanumeric characters and
+/// underscores, at least one of which is a letter.
+fn find_tags(text: &str) -> Vec<Range<usize>> {
+    let mut tags = Vec::new();
+    let mut prev = None;
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if c == '#' && !prev.is_some_and(|p: char| p.is_alphanumeric() || p == '#' || p == '&') {
+            let mut end = start + 1;
+            while let Some(&(i, c)) = chars.peek() {
+                if !(c.is_alphanumeric() || c == '_') {
+                    break;
+                }
+                end = i + c.len_utf8();
+                chars.next();
+            }
+            if text[start + 1..end].chars().any(char::is_alphabetic) {
+                tags.push(start..end);
+            }
+            prev = text[..end].chars().next_back();
+        } else {
+            prev = Some(c);
+        }
+    }
+    tags
 }
 
 #[cfg(test)]
 mod tests {
+    use time::format_description::well_known::Rfc3339;
+
     use super::*;
 
     #[test]
@@ -198,9 +1306,47 @@ mod tests {
             note_id: PublicId::random(),
             body: r#"It's ~~not~~ _electric_!"#.into(),
             created_at: OffsetDateTime::now_utc(),
+            updated_at: None,
+            status: NoteStatus::Published,
+            publish_at: None,
+        };
+
+        assert_eq!(
+            note.to_html(&ResponsiveImages::default()),
+            "<p>It’s <del>not</del> <em>electric</em>!</p>\n"
+        );
+    }
+
+    #[test]
+    fn body_to_tags() {
+        let note = Note {
+            note_id: PublicId::random(),
+            body: "#Hello, #world! Not C# or &#35; or [#linked](/x) or `#code` or #123. #world"
+                .into(),
+            created_at: OffsetDateTime::now_utc(),
+            updated_at: None,
+            status: NoteStatus::Published,
+            publish_at: None,
         };
 
-        assert_eq!(note.to_html(), "<p>It’s <del>not</del> <em>electric</em>!</p>\n");
+        assert_eq!(parse_tags(&note.body), vec!["hello", "world"]);
+        assert_eq!(
+            note.to_html(&ResponsiveImages::default()),
+            concat!(
+                r#"<p><a href="/tags/hello">#Hello</a>, <a href="/tags/world">#world</a>! "#,
+                r##"Not C# or # or <a href="/x">#linked</a> or <code>#code</code> or #123. "##,
+                r#"<a href="/tags/world">#world</a></p>"#,
+                "\n"
+            )
+        );
+    }
+
+    #[test]
+    fn snippet_to_html() {
+        assert_eq!(
+            highlight_snippet("…it's a \x02<b>me\x03, \x02Mario\x03."),
+            "…it's a <mark>&lt;b&gt;me</mark>, <mark>Mario</mark>."
+        );
     }
 
     #[test]
@@ -209,8 +1355,108 @@ mod tests {
             note_id: PublicId::random(),
             body: "It's _electric_!\n\nBoogie woogie woogie.".into(),
             created_at: OffsetDateTime::now_utc(),
+            updated_at: None,
+            status: NoteStatus::Published,
+            publish_at: None,
         };
 
         assert_eq!(note.description(), r#"It’s electric! Boogie woogie woogie."#);
     }
+
+    #[test]
+    fn body_to_title() -> Result<(), anyhow::Error> {
+        let note = |body: &str| Note {
+            note_id: PublicId::random(),
+            body: body.into(),
+            created_at: OffsetDateTime::now_utc(),
+            updated_at: None,
+            status: NoteStatus::Published,
+            publish_at: None,
+        };
+
+        let utc = TimeZone::utc();
+        assert_eq!(note("Intro.\n\n## It's _electric_!\n\nBoogie.").title(&utc), "It’s electric!");
+        assert_eq!(note("It's _electric_!").title(&utc), "It’s electric!");
+        assert_eq!(
+            note(&"Boogie woogie woogie, ".repeat(5)).title(&utc),
+            "Boogie woogie woogie, Boogie woogie woogie, Boogie woogie woogie, Boogie woogie…"
+        );
+        assert_eq!(note(&"a".repeat(100)).title(&utc), format!("{}…", "a".repeat(80)));
+        assert_eq!(
+            note(&format!("# {}", "a".repeat(100))).title(&utc),
+            format!("{}…", "a".repeat(80))
+        );
+
+        // Notes with only media fall back to alt text, then the date.
+        let image = "![A cat.](/images/4c89cfef-9031-49c0-8b91-2578c0e227f3.main.webp)";
+        assert_eq!(note(image).title(&utc), "A cat.");
+        assert_eq!(note(&format!("![](/a.webp)\n\n{image}")).title(&utc), "A cat.");
+        let audio = note("![](/images/4c89cfef-9031-49c0-8b91-2578c0e227f3.audio.mp3)");
+        let audio =
+            Note { created_at: OffsetDateTime::parse("2022-11-15T03:00:00Z", &Rfc3339)?, ..audio };
+        assert_eq!(audio.title(&utc), "November 15, 2022");
+        assert_eq!(audio.title(&mountain_time()), "November 14, 2022");
+
+        Ok(())
+    }
+
+    #[test]
+    fn body_to_images() {
+        let note = Note {
+            note_id: PublicId::random(),
+            body: "![A *cat* in a hat.](/images/cat.webp)\n\n![](https://example.org/dog.webp)"
+                .into(),
+            created_at: OffsetDateTime::now_utc(),
+            updated_at: None,
+            status: NoteStatus::Published,
+            publish_at: None,
+        };
+
+        let images = note.images(&"http://example.com".parse().unwrap());
+        assert_eq!(
+            images.iter().map(|i| (i.url.as_str(), i.alt.as_str())).collect::<Vec<_>>(),
+            vec![
+                ("http://example.com/images/cat.webp", "A cat in a hat."),
+                ("https://example.org/dog.webp", "")
+            ]
+        );
+    }
+
+    fn mountain_time() -> TimeZone {
+        TimeZone::from_posix_tz("MST7MDT,M3.2.0,M11.1.0").expect("should be a valid time zone")
+    }
+
+    #[test]
+    fn local_time_across_dst() {
+        let tz = mountain_time();
+        let summer = OffsetDateTime::from_unix_timestamp(1_657_843_200).unwrap(); // 2022-07-15 00:00Z
+        let winter = OffsetDateTime::from_unix_timestamp(1_673_740_800).unwrap(); // 2023-01-15 00:00Z
+
+        assert_eq!(to_local(&tz, summer).unwrap().offset().whole_hours(), -6);
+        assert_eq!(to_local(&tz, winter).unwrap().offset().whole_hours(), -7);
+        assert_eq!(to_local(&tz, summer).unwrap().date().to_string(), "2022-07-14");
+    }
+
+    #[test]
+    fn start_of_day_across_dst() {
+        let tz = mountain_time();
+        let day = |s| Date::parse(s, &time::format_description::well_known::Iso8601::DATE).unwrap();
+
+        // The day DST starts and the day it ends both begin at midnight at the previous offset.
+        assert_eq!(start_of_day(&tz, day("2022-03-13")).unwrap().unix_timestamp(), 1_647_154_800);
+        assert_eq!(start_of_day(&tz, day("2022-03-14")).unwrap().unix_timestamp(), 1_647_237_600);
+        assert_eq!(start_of_day(&tz, day("2022-11-06")).unwrap().unix_timestamp(), 1_667_714_400);
+        assert_eq!(start_of_day(&tz, day("2022-11-07")).unwrap().unix_timestamp(), 1_667_804_400);
+    }
+
+    #[test]
+    fn start_of_skipped_midnight() {
+        // Chilean clocks skip from midnight to 1am on the first Sunday in September.
+        let tz = TimeZone::from_posix_tz("<-04>4<-03>,M9.1.6/24,M4.1.6/24")
+            .expect("should be a valid time zone");
+        let start = start_of_day(&tz, Date::from_calendar_date(2022, Month::September, 4).unwrap())
+            .unwrap();
+
+        assert_eq!(start.unix_timestamp(), 1_662_264_000);
+    }
 }
@@ -16,7 +16,7 @@ use tracing_subscriber::{
     EnvFilter, fmt::format::FmtSpan, layer::SubscriberExt, util::SubscriberInitExt,
 };
 
-use crate::{config::Config, web::AppState};
+use crate::{config::Config, services::images::ImageProcessor, web::AppState};
 
 pub struct TestEnv {
     pub db: Connection,
@@ -32,6 +32,10 @@ impl TestEnv {
             Config::try_parse_from::<_, OsString>([]).expect("should parse empty command line");
         config.data_dir = temp_dir.path().to_path_buf();
         config.base_url = "http://example.com".parse().expect("should be a valid URL");
+        // ImageMagick isn't required to run the tests.
+        config.image_processor = ImageProcessor::Native;
+        config.image_download_allowlist =
+            vec!["127.0.0.0/8".parse().expect("should be a valid net")];
         let mut db = Connection::open_in_memory().await?;
         let migrations = AsyncMigrations::from_directory(&MIGRATIONS_DIR)?;
         migrations.to_latest(&mut db).await?;
@@ -2,28 +2,49 @@ use anyhow::Context;
 use askama::Template;
 use axum::{
     Form, Router,
-    extract::{DefaultBodyLimit, Multipart, State},
+    extract::{DefaultBodyLimit, Multipart, Path, Query, Request, State},
     http::StatusCode,
     response::{IntoResponse, Redirect, Response},
     routing::{get, post},
 };
 use mime::Mime;
 use serde::Deserialize;
-use time::OffsetDateTime;
-use tower::ServiceBuilder;
-use tower_http::limit::RequestBodyLimitLayer;
-use url::Url;
+use serde_with::{NoneAsEmptyString, serde_as};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+use tower::{ServiceBuilder, ServiceExt};
+use tower_http::{limit::RequestBodyLimitLayer, services::ServeFile};
+use tz::TimeZone;
+use url::{Url, form_urlencoded};
 
 use crate::{
     id::PublicId,
-    services::{images::Image, notes::Note},
-    web::app::{AppError, AppState, Page},
+    services::{
+        images::{Image, ImageStatus, ResponsiveImages},
+        notes::{Note, NoteStatus, Revision},
+    },
+    web::{
+        app::{AppError, AppState, Page},
+        feed::filters,
+    },
 };
 
 pub fn router() -> Router<AppState> {
     Router::new()
         .route("/admin/new", get(new_page))
         .route("/admin/new-note", post(create_note))
+        .route("/admin/drafts", get(drafts_page).post(save_draft))
+        .route("/admin/notes", get(notes_page))
+        .route("/admin/scheduled", get(scheduled_page))
+        .route("/admin/deleted", get(deleted_page))
+        .route("/admin/note/{note_id}/edit", get(edit_page).post(update_note))
+        .route("/admin/note/{note_id}/cancel", post(cancel_note))
+        .route("/admin/note/{note_id}/delete", get(confirm_delete).post(delete_note))
+        .route("/admin/note/{note_id}/purge", get(confirm_purge).post(purge_note))
+        .route("/admin/images", get(images_page))
+        .route("/admin/image/{image_id}", get(image_page).post(describe_image))
+        .route("/admin/image/{image_id}/original", get(image_original))
+        .route("/admin/image/{image_id}/retry", post(retry_image))
+        .route("/admin/image/{image_id}/delete", post(delete_image))
         .route("/admin/upload-images", post(upload_images))
         .route("/admin/download-image", post(download_image))
         .layer(
@@ -37,22 +58,131 @@ pub fn router() -> Router<AppState> {
 #[template(path = "new.html")]
 struct NewPage {
     images: Vec<Image>,
+    draft: Option<Note>,
+    note: Option<Note>,
+    revisions: Vec<Revision>,
+    time_zone: TimeZone,
 }
 
-async fn new_page(state: State<AppState>) -> Result<Page<NewPage>, AppError> {
-    Ok(Page(NewPage { images: state.images.most_recent(10).await? }))
+#[derive(Debug, Deserialize)]
+struct NewOpts {
+    draft: Option<String>,
+}
+
+async fn new_page(
+    state: State<AppState>,
+    Query(opts): Query<NewOpts>,
+) -> Result<Page<NewPage>, AppError> {
+    let draft = match opts.draft {
+        Some(draft_id) => Some(
+            state
+                .notes
+                .by_id(&draft_id)
+                .await?
+                .filter(|n| n.status == NoteStatus::Draft)
+                .ok_or(AppError::NotFound)?,
+        ),
+        None => None,
+    };
+    let images = state.images.most_recent(10).await?;
+    let time_zone = state.config.time_zone.clone();
+    Ok(Page(NewPage { images, draft, note: None, revisions: vec![], time_zone }))
+}
+
+#[derive(Debug, Template)]
+#[template(path = "notes.html")]
+struct NotesPage {
+    heading: &'static str,
+    notes: Vec<Note>,
+    time_zone: TimeZone,
+}
+
+async fn notes_page(state: State<AppState>) -> Result<Page<NotesPage>, AppError> {
+    let notes = state.notes.most_recent(100).await?;
+    Ok(Page(NotesPage { heading: "Notes", notes, time_zone: state.config.time_zone.clone() }))
 }
 
+async fn drafts_page(state: State<AppState>) -> Result<Page<NotesPage>, AppError> {
+    let notes = state.notes.by_status(NoteStatus::Draft, 100).await?;
+    Ok(Page(NotesPage { heading: "Drafts", notes, time_zone: state.config.time_zone.clone() }))
+}
+
+#[serde_as]
+#[derive(Debug, Deserialize)]
+struct Draft {
+    #[serde_as(as = "NoneAsEmptyString")]
+    #[serde(default)]
+    draft_id: Option<PublicId>,
+    body: String,
+}
+
+async fn save_draft(state: State<AppState>, Form(draft): Form<Draft>) -> Result<String, AppError> {
+    Ok(state.notes.save_draft(draft.draft_id, draft.body).await?.to_string())
+}
+
+async fn scheduled_page(state: State<AppState>) -> Result<Page<NotesPage>, AppError> {
+    let notes = state.notes.by_status(NoteStatus::Scheduled, 100).await?;
+    Ok(Page(NotesPage {
+        heading: "Scheduled Notes",
+        notes,
+        time_zone: state.config.time_zone.clone(),
+    }))
+}
+
+async fn cancel_note(
+    state: State<AppState>,
+    Path(note_id): Path<String>,
+) -> Result<Redirect, AppError> {
+    if state.notes.cancel_schedule(&note_id).await? {
+        Ok(Redirect::to(&format!("/admin/new?draft={note_id}")))
+    } else {
+        Err(AppError::NotFound)
+    }
+}
+
+async fn deleted_page(state: State<AppState>) -> Result<Page<NotesPage>, AppError> {
+    let notes = state.notes.by_status(NoteStatus::Deleted, 100).await?;
+    Ok(Page(NotesPage {
+        heading: "Deleted Notes",
+        notes,
+        time_zone: state.config.time_zone.clone(),
+    }))
+}
+
+async fn edit_page(
+    state: State<AppState>,
+    Path(note_id): Path<String>,
+) -> Result<Response, AppError> {
+    let note = state.notes.by_id(&note_id).await?.ok_or(AppError::NotFound)?;
+    if note.status == NoteStatus::Draft {
+        return Ok(Redirect::to(&format!("/admin/new?draft={note_id}")).into_response());
+    }
+    let revisions = state.notes.revisions(&note_id).await?;
+    let images = state.images.most_recent(10).await?;
+    let time_zone = state.config.time_zone.clone();
+    Ok(Page(NewPage { images, draft: None, note: Some(note), revisions, time_zone })
+        .into_response())
+}
+
+#[serde_as]
 #[derive(Debug, Deserialize)]
 struct NewNote {
     body: String,
     preview: bool,
+    #[serde_as(as = "NoneAsEmptyString")]
+    #[serde(default)]
+    draft_id: Option<PublicId>,
+    #[serde_as(as = "NoneAsEmptyString")]
+    #[serde(default)]
+    publish_at: Option<String>,
 }
 
 #[derive(Debug, Template)]
 #[template(path = "preview.html")]
 struct PreviewPage {
     note: Note,
+    images: ResponsiveImages,
+    edit_url: String,
 }
 
 async fn create_note(
@@ -60,35 +190,276 @@ async fn create_note(
     Form(new_note): Form<NewNote>,
 ) -> Result<Response, AppError> {
     if new_note.preview {
+        let draft_id = state.notes.save_draft(new_note.draft_id, new_note.body.clone()).await?;
         let note = Note {
-            note_id: PublicId::random(),
+            note_id: draft_id,
             body: new_note.body,
             created_at: OffsetDateTime::now_utc(),
+            updated_at: None,
+            status: NoteStatus::Draft,
+            publish_at: None,
+        };
+        let images = state.images.responsive(note.image_srcs()).await?;
+        let edit_url = format!("/admin/new?draft={draft_id}");
+        Ok(Page(PreviewPage { note, images, edit_url }).into_response())
+    } else if let Some(publish_at) = new_note.publish_at {
+        let Ok(publish_at) = OffsetDateTime::parse(&publish_at, &Rfc3339) else {
+            return Ok(StatusCode::BAD_REQUEST.into_response());
         };
-        Ok(Page(PreviewPage { note }).into_response())
+        state.notes.schedule(new_note.draft_id, new_note.body, publish_at).await?;
+        Ok(Redirect::to("/admin/scheduled").into_response())
+    } else if let Some(draft_id) = new_note.draft_id {
+        if state.notes.publish(draft_id, new_note.body).await? {
+            Ok(Redirect::to(&format!("/note/{draft_id}")).into_response())
+        } else {
+            Err(AppError::NotFound)
+        }
     } else {
         let note_id = state.notes.create(new_note.body).await?;
         Ok(Redirect::to(&format!("/note/{note_id}")).into_response())
     }
 }
 
+async fn update_note(
+    state: State<AppState>,
+    Path(note_id): Path<String>,
+    Form(new_note): Form<NewNote>,
+) -> Result<Response, AppError> {
+    if new_note.preview {
+        let mut note = state.notes.by_id(&note_id).await?.ok_or(AppError::NotFound)?;
+        note.body = new_note.body;
+        note.updated_at = Some(OffsetDateTime::now_utc());
+        let images = state.images.responsive(note.image_srcs()).await?;
+        let edit_url = format!("/admin/note/{note_id}/edit");
+        Ok(Page(PreviewPage { note, images, edit_url }).into_response())
+    } else if state.notes.update(&note_id, new_note.body).await? {
+        Ok(Redirect::to(&format!("/note/{note_id}")).into_response())
+    } else {
+        Err(AppError::NotFound)
+    }
+}
+
+#[derive(Debug, Template)]
+#[template(path = "images.html")]
+struct ImagesPage {
+    images: Vec<Image>,
+    query: String,
+    older: Option<String>,
+    newer: Option<String>,
+    time_zone: TimeZone,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImagesOpts {
+    #[serde(default)]
+    q: String,
+    page: Option<u32>,
+}
+
+/// The number of images on a page of the image library.
+const IMAGES_PAGE_SIZE: u16 = 20;
+
+async fn images_page(
+    state: State<AppState>,
+    Query(opts): Query<ImagesOpts>,
+) -> Result<Page<ImagesPage>, AppError> {
+    let page = opts.page.unwrap_or(1).max(1);
+    let offset = (page - 1).saturating_mul(IMAGES_PAGE_SIZE.into());
+
+    // Fetch one more image than needed to see if there's another page.
+    let mut images = state.images.search(opts.q.clone(), offset, IMAGES_PAGE_SIZE + 1).await?;
+    let has_older = images.len() > IMAGES_PAGE_SIZE.into();
+    images.truncate(IMAGES_PAGE_SIZE.into());
+
+    let page_url = |page: u32| {
+        let mut query = form_urlencoded::Serializer::new(String::new());
+        if !opts.q.is_empty() {
+            query.append_pair("q", &opts.q);
+        }
+        query.append_pair("page", &page.to_string());
+        format!("/admin/images?{}", query.finish())
+    };
+    let older = has_older.then(|| page_url(page + 1));
+    let newer = (page > 1).then(|| page_url(page - 1));
+
+    let time_zone = state.config.time_zone.clone();
+    Ok(Page(ImagesPage { images, query: opts.q, older, newer, time_zone }))
+}
+
+#[derive(Debug, Template)]
+#[template(path = "image.html")]
+struct ImagePage {
+    image: Image,
+    notes: Vec<Note>,
+    time_zone: TimeZone,
+}
+
+async fn image_page(
+    state: State<AppState>,
+    Path(image_id): Path<String>,
+) -> Result<Page<ImagePage>, AppError> {
+    let image = state.images.by_id(&image_id).await?.ok_or(AppError::NotFound)?;
+
+    // Find the notes which embed the image.
+    let base_url = &state.config.base_url;
+    let embed_url = base_url.join(&image.embed_src()).context("invalid image URL")?;
+    let notes = state
+        .notes
+        .containing(&image.image_id.to_string())
+        .await?
+        .into_iter()
+        .filter(|n| n.images(base_url).iter().any(|i| i.url == embed_url))
+        .collect();
+
+    Ok(Page(ImagePage { image, notes, time_zone: state.config.time_zone.clone() }))
+}
+
+async fn image_original(
+    state: State<AppState>,
+    Path(image_id): Path<String>,
+    req: Request,
+) -> Result<Response, AppError> {
+    let image = state.images.by_id(&image_id).await?.ok_or(AppError::NotFound)?;
+    let content_type = image.content_type.parse::<Mime>().context("invalid content type")?;
+    let path = state.images.original(&image)?;
+    Ok(ServeFile::new_with_mime(path, &content_type).oneshot(req).await.into_response())
+}
+
+async fn delete_image(
+    state: State<AppState>,
+    Path(image_id): Path<String>,
+) -> Result<Redirect, AppError> {
+    if state.images.delete(&image_id).await? {
+        Ok(Redirect::to("/admin/images"))
+    } else {
+        Err(AppError::NotFound)
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ImageDescription {
+    #[serde(default)]
+    alt_text: String,
+    #[serde(default)]
+    caption: String,
+}
+
+async fn describe_image(
+    state: State<AppState>,
+    Path(image_id): Path<String>,
+    Form(desc): Form<ImageDescription>,
+) -> Result<Redirect, AppError> {
+    if state.images.describe(&image_id, desc.alt_text, desc.caption).await? {
+        Ok(Redirect::to(&format!("/admin/image/{image_id}")))
+    } else {
+        Err(AppError::NotFound)
+    }
+}
+
+async fn retry_image(
+    state: State<AppState>,
+    Path(image_id): Path<String>,
+) -> Result<Redirect, AppError> {
+    if state.images.retry(&image_id).await? {
+        Ok(Redirect::to("/admin/new"))
+    } else {
+        Err(AppError::NotFound)
+    }
+}
+
 async fn upload_images(
     state: State<AppState>,
     mut multipart: Multipart,
 ) -> Result<Redirect, AppError> {
+    // The description fields precede the files in the form, so they're known by the time the files
+    // are processed.
+    let mut desc = ImageDescription::default();
     while let Some(field) = multipart.next_field().await.context("multipart error")? {
-        if let Some(content_type) = field.content_type().and_then(|s| s.parse::<Mime>().ok())
-            && content_type.type_() == mime::IMAGE {
-                let original_filename = field.file_name().unwrap_or("none").to_string();
-                state.images.add(original_filename, content_type, field).await?;
+        match field.name() {
+            Some("alt_text") => desc.alt_text = field.text().await.context("multipart error")?,
+            Some("caption") => desc.caption = field.text().await.context("multipart error")?,
+            _ => {
+                if let Some(content_type) =
+                    field.content_type().and_then(|s| s.parse::<Mime>().ok())
+                    && [mime::IMAGE, mime::VIDEO, mime::AUDIO].contains(&content_type.type_())
+                {
+                    let original_filename = field.file_name().unwrap_or("none").to_string();
+                    let (alt_text, caption) = (desc.alt_text.clone(), desc.caption.clone());
+                    state
+                        .images
+                        .add(original_filename, content_type, alt_text, caption, field)
+                        .await?;
+                }
             }
+        }
     }
     Ok(Redirect::to("/admin/new"))
 }
 
+#[derive(Debug, Template)]
+#[template(path = "confirm.html")]
+struct ConfirmPage {
+    note: Note,
+    images: ResponsiveImages,
+    action: &'static str,
+    prompt: &'static str,
+}
+
+async fn confirm_delete(
+    state: State<AppState>,
+    Path(note_id): Path<String>,
+) -> Result<Page<ConfirmPage>, AppError> {
+    let note = state.notes.by_id(&note_id).await?.ok_or(AppError::NotFound)?;
+    let images = state.images.responsive(note.image_srcs()).await?;
+    Ok(Page(ConfirmPage {
+        note,
+        images,
+        action: "delete",
+        prompt: "Delete this note? It will be replaced with a tombstone.",
+    }))
+}
+
+async fn delete_note(
+    state: State<AppState>,
+    Path(note_id): Path<String>,
+) -> Result<Redirect, AppError> {
+    if state.notes.delete(&note_id).await? {
+        Ok(Redirect::to("/admin/deleted"))
+    } else {
+        Err(AppError::NotFound)
+    }
+}
+
+async fn confirm_purge(
+    state: State<AppState>,
+    Path(note_id): Path<String>,
+) -> Result<Page<ConfirmPage>, AppError> {
+    let note = state.notes.by_id(&note_id).await?.ok_or(AppError::NotFound)?;
+    let images = state.images.responsive(note.image_srcs()).await?;
+    Ok(Page(ConfirmPage {
+        note,
+        images,
+        action: "purge",
+        prompt: "Purge this note? It and all its revisions will be gone forever.",
+    }))
+}
+
+async fn purge_note(
+    state: State<AppState>,
+    Path(note_id): Path<String>,
+) -> Result<Redirect, AppError> {
+    if state.notes.purge(&note_id).await? {
+        Ok(Redirect::to("/admin/deleted"))
+    } else {
+        Err(AppError::NotFound)
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct DownloadImage {
     url: String,
+    #[serde(flatten)]
+    desc: ImageDescription,
 }
 
 async fn download_image(
@@ -96,7 +467,7 @@ async fn download_image(
     Form(image): Form<DownloadImage>,
 ) -> Result<Response, AppError> {
     if let Ok(url) = image.url.parse::<Url>() {
-        state.images.download(url).await?;
+        state.images.download(url, image.desc.alt_text, image.desc.caption).await?;
         Ok(Redirect::to("/admin/new").into_response())
     } else {
         Ok(StatusCode::BAD_REQUEST.into_response())
@@ -107,12 +478,40 @@ async fn download_image(
 mod tests {
     use axum::routing::get_service;
     use reqwest::{StatusCode, header, multipart};
+    use rusqlite::params;
     use tokio::fs;
-    use tower_http::services::ServeFile;
 
     use super::*;
     use crate::test::TestEnv;
 
+    #[test]
+    fn local_times() -> Result<(), anyhow::Error> {
+        let created_at = OffsetDateTime::parse("2022-11-06T05:30:00Z", &Rfc3339)?;
+        let note = |status| Note {
+            note_id: PublicId::random(),
+            body: "Saturday night.".into(),
+            created_at,
+            updated_at: None,
+            status,
+            publish_at: Some(created_at),
+        };
+        let page = NotesPage {
+            heading: "Notes",
+            notes: vec![
+                note(NoteStatus::Published),
+                note(NoteStatus::Draft),
+                note(NoteStatus::Scheduled),
+                note(NoteStatus::Deleted),
+            ],
+            time_zone: TimeZone::from_posix_tz("MST7MDT,M3.2.0,M11.1.0")?,
+        }
+        .render()?;
+        assert_eq!(page.matches("2022-11-05 23:30:00.0 -06:00:00").count(), 4);
+        assert!(!page.contains("+00:00:00"));
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn new_note_ui() -> Result<(), anyhow::Error> {
         let ts = TestEnv::new().await?.into_server(router()).await?;
@@ -123,8 +522,8 @@ values ('4c89cfef-9031-49c0-8b91-2578c0e227f3', 'garfield-pantless.webp', 'image
 insert into image (image_id, original_filename, content_type, created_at)
 values ('7963d8bc-9cf8-4459-a593-b6d49b94b541', 'garfield-john-rodeo.jpg', 'image/jpeg', datetime('now', '-2 days'));
 
-insert into image (image_id, original_filename, content_type, created_at)
-values ('cbdc5a69-abba-4d75-9679-44259c48b272', 'garfield-odie-whips.bmp', 'image/bmp', datetime('now', '-3 days'));
+insert into image (image_id, original_filename, content_type, alt_text, created_at)
+values ('cbdc5a69-abba-4d75-9679-44259c48b272', 'garfield-odie-whips.bmp', 'image/bmp', 'Garfield whips Odie.', datetime('now', '-3 days'));
 "#)).await?;
 
         let resp = ts.get("/admin/new").send().await?;
@@ -132,6 +531,145 @@ values ('cbdc5a69-abba-4d75-9679-44259c48b272', 'garfield-odie-whips.bmp', 'imag
 
         let body = resp.text().await?;
         assert!(body.contains("/images/cbdc5a69-abba-4d75-9679-44259c48b272.thumb.webp"));
+        assert!(body.contains(r#"data-alt="Garfield whips Odie.""#));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn describing_an_image() -> Result<(), anyhow::Error> {
+        let ts = TestEnv::new().await?.into_server(router()).await?;
+        ts.db
+            .call_unwrap(|conn| {
+                conn.execute_batch(
+                    r#"
+insert into image (image_id, original_filename, content_type)
+values ('4c89cfef-9031-49c0-8b91-2578c0e227f3', 'garfield-pantless.webp', 'image/webp');
+"#,
+                )
+            })
+            .await?;
+
+        let resp = ts
+            .post("/admin/image/4c89cfef-9031-49c0-8b91-2578c0e227f3")
+            .form(&[("alt_text", "Garfield without pants."), ("caption", "Scandalous.")])
+            .send()
+            .await?;
+        assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+
+        let recent = ts.state.images.most_recent(1).await?;
+        assert_eq!(recent[0].alt_text, "Garfield without pants.");
+        assert_eq!(recent[0].caption, "Scandalous.");
+
+        let resp = ts.get("/admin/image/4c89cfef-9031-49c0-8b91-2578c0e227f3").send().await?;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(resp.text().await?.contains(r#"value="Garfield without pants.""#));
+
+        let resp = ts
+            .post("/admin/image/37c615b0-bb55-424d-a813-69e14ca5c20c")
+            .form(&[("alt_text", "Nothing.")])
+            .send()
+            .await?;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn image_library() -> Result<(), anyhow::Error> {
+        let ts = TestEnv::new().await?.into_server(router()).await?;
+        ts.db
+            .call_unwrap(|conn| -> Result<(), rusqlite::Error> {
+                for i in 0..25 {
+                    conn.execute(
+                        r#"
+                        insert into image (image_id, original_filename, content_type, created_at)
+                        values (?, ?, 'image/png', datetime('now', ?))
+                        "#,
+                        params![
+                            PublicId::random(),
+                            format!("garfield-{i:02}.png"),
+                            format!("-{i} minutes")
+                        ],
+                    )?;
+                }
+                Ok(())
+            })
+            .await?;
+
+        let resp = ts.get("/admin/images").send().await?;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.text().await?;
+        assert!(body.contains("garfield-00.png"));
+        assert!(body.contains("garfield-19.png"));
+        assert!(!body.contains("garfield-20.png"));
+        assert!(body.contains("/admin/images?page=2"));
+
+        let resp = ts.get("/admin/images?page=2").send().await?;
+        let body = resp.text().await?;
+        assert!(!body.contains("garfield-19.png"));
+        assert!(body.contains("garfield-24.png"));
+        assert!(body.contains("/admin/images?page=1"));
+        assert!(!body.contains("/admin/images?page=3"));
+
+        let resp = ts.get("/admin/images?q=GARFIELD-1").send().await?;
+        let body = resp.text().await?;
+        assert!(body.contains("garfield-10.png"));
+        assert!(body.contains("garfield-19.png"));
+        assert!(!body.contains("garfield-09.png"));
+        assert!(!body.contains("page="));
+
+        let resp = ts.get("/admin/images?q=odie").send().await?;
+        assert!(resp.text().await?.contains("Nothing matched."));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn viewing_and_deleting_an_image() -> Result<(), anyhow::Error> {
+        let ts = TestEnv::new().await?.into_server(router()).await?;
+
+        let img = fs::read("yellhole.webp").await?;
+        let form = multipart::Form::new().part(
+            "one",
+            multipart::Part::bytes(img.clone()).file_name("example.webp").mime_str("image/webp")?,
+        );
+        let resp = ts.post("/admin/upload-images").multipart(form).send().await?;
+        assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+        ts.state.images.process_queued().await?;
+
+        let image = ts.state.images.most_recent(1).await?.remove(0);
+        let image_id = image.image_id;
+        let note_id =
+            ts.state.notes.create(format!("![A hole.]({})", image.main_src())).await?.to_string();
+        ts.state.notes.create(format!("I uploaded {image_id} today.")).await?;
+
+        let resp = ts.get(&format!("/admin/image/{image_id}")).send().await?;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.text().await?;
+        assert!(body.contains(&image.thumbnail_src()));
+        assert!(body.contains(&format!("/note/{note_id}")));
+        assert!(!body.contains("I uploaded"));
+
+        let resp = ts.get(&format!("/admin/image/{image_id}/original")).send().await?;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).map(|h| h.as_bytes()),
+            Some("image/webp".as_bytes())
+        );
+        assert_eq!(resp.bytes().await?, img);
+
+        let resp = ts.post(&format!("/admin/image/{image_id}/delete")).send().await?;
+        assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+        assert!(ts.state.images.most_recent(1).await?.is_empty());
+        let data_dir = &ts.state.config.data_dir;
+        assert_eq!(std::fs::read_dir(data_dir.join("images"))?.count(), 0);
+        assert_eq!(std::fs::read_dir(data_dir.join("uploads"))?.count(), 0);
+
+        let resp = ts.get(&format!("/admin/image/{image_id}")).send().await?;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let resp = ts.post(&format!("/admin/image/{image_id}/delete")).send().await?;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
 
         Ok(())
     }
@@ -157,12 +695,195 @@ values ('cbdc5a69-abba-4d75-9679-44259c48b272', 'garfield-odie-whips.bmp', 'imag
         Ok(())
     }
 
+    #[tokio::test]
+    async fn drafting_a_note() -> Result<(), anyhow::Error> {
+        let ts = TestEnv::new().await?.into_server(router()).await?;
+
+        let resp =
+            ts.post("/admin/drafts").form(&[("draft_id", ""), ("body", "This is")]).send().await?;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let draft_id = resp.text().await?;
+
+        let resp = ts
+            .post("/admin/drafts")
+            .form(&[("draft_id", draft_id.as_str()), ("body", "This is a draft.")])
+            .send()
+            .await?;
+        assert_eq!(resp.text().await?, draft_id);
+
+        let resp = ts.get(&format!("/admin/new?draft={draft_id}")).send().await?;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(resp.text().await?.contains("This is a draft."));
+
+        let resp = ts.get("/admin/drafts").send().await?;
+        assert!(resp.text().await?.contains(&format!("/admin/new?draft={draft_id}")));
+        assert!(ts.state.notes.most_recent(20).await?.is_empty());
+
+        let resp = ts
+            .post("/admin/new-note")
+            .form(&[
+                ("draft_id", draft_id.as_str()),
+                ("body", "This is a note."),
+                ("preview", "false"),
+            ])
+            .send()
+            .await?;
+        assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+        assert_eq!(
+            resp.headers().get(header::LOCATION).map(|h| h.as_bytes()),
+            Some(format!("/note/{draft_id}").as_bytes())
+        );
+
+        let note = ts.state.notes.by_id(&draft_id).await?.expect("missing note");
+        assert_eq!(note.status, NoteStatus::Published);
+        assert_eq!(note.body, "This is a note.");
+        assert!(ts.state.notes.by_status(NoteStatus::Draft, 20).await?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn scheduling_a_note() -> Result<(), anyhow::Error> {
+        let ts = TestEnv::new().await?.into_server(router()).await?;
+
+        let resp = ts
+            .post("/admin/new-note")
+            .form(&[
+                ("body", "This is from the future."),
+                ("preview", "false"),
+                ("publish_at", "2999-01-01T00:00:00Z"),
+            ])
+            .send()
+            .await?;
+        assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+
+        let scheduled = ts.state.notes.by_status(NoteStatus::Scheduled, 20).await?;
+        assert_eq!(scheduled.len(), 1);
+        let note_id = scheduled[0].note_id.to_string();
+
+        assert_eq!(ts.state.notes.publish_scheduled().await?, 0);
+        assert!(ts.state.notes.most_recent(20).await?.is_empty());
+
+        let resp = ts.get("/admin/scheduled").send().await?;
+        assert!(resp.text().await?.contains(&format!("/admin/note/{note_id}/cancel")));
+
+        let resp = ts.post(&format!("/admin/note/{note_id}/cancel")).send().await?;
+        assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+        let note = ts.state.notes.by_id(&note_id).await?.expect("missing note");
+        assert_eq!(note.status, NoteStatus::Draft);
+
+        let resp = ts
+            .post("/admin/new-note")
+            .form(&[
+                ("draft_id", note_id.as_str()),
+                ("body", "This is from the past."),
+                ("preview", "false"),
+                ("publish_at", "2022-11-14T18:22:00-07:00"),
+            ])
+            .send()
+            .await?;
+        assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+
+        assert_eq!(ts.state.notes.publish_scheduled().await?, 1);
+        let recent = ts.state.notes.most_recent(20).await?;
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].body, "This is from the past.");
+        assert_eq!(recent[0].created_at, OffsetDateTime::parse("2022-11-15T01:22:00Z", &Rfc3339)?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn editing_a_note() -> Result<(), anyhow::Error> {
+        let ts = TestEnv::new().await?.into_server(router()).await?;
+        let note_id = ts.state.notes.create("This is a nite.".into()).await?.to_string();
+
+        let resp = ts.get(&format!("/admin/note/{note_id}/edit")).send().await?;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(resp.text().await?.contains("This is a nite."));
+
+        let resp = ts
+            .post(&format!("/admin/note/{note_id}/edit"))
+            .form(&[("body", "This is a note."), ("preview", "false")])
+            .send()
+            .await?;
+        assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+
+        let note = ts.state.notes.by_id(&note_id).await?.expect("missing note");
+        assert_eq!(note.body, "This is a note.");
+        assert!(note.updated_at.is_some());
+
+        let revisions = ts.state.notes.revisions(&note_id).await?;
+        assert_eq!(revisions.len(), 1);
+        assert_eq!(revisions[0].body, "This is a nite.");
+
+        // Saving an unchanged body doesn't record a revision.
+        let resp = ts
+            .post(&format!("/admin/note/{note_id}/edit"))
+            .form(&[("body", "This is a note."), ("preview", "false")])
+            .send()
+            .await?;
+        assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+        assert_eq!(ts.state.notes.revisions(&note_id).await?.len(), 1);
+
+        // Deleted notes can't be edited back into the feed.
+        assert!(ts.state.notes.delete(&note_id).await?);
+        let resp = ts
+            .post(&format!("/admin/note/{note_id}/edit"))
+            .form(&[("body", "This is a new note."), ("preview", "false")])
+            .send()
+            .await?;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let note = ts.state.notes.by_id(&note_id).await?.expect("missing note");
+        assert_eq!(note.body, "This is a note.");
+
+        let resp = ts
+            .post("/admin/note/37c615b0-bb55-424d-a813-69e14ca5c20c/edit")
+            .form(&[("body", "This is a note."), ("preview", "false")])
+            .send()
+            .await?;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn deleting_and_purging_a_note() -> Result<(), anyhow::Error> {
+        let ts = TestEnv::new().await?.into_server(router()).await?;
+        let note_id = ts.state.notes.create("This is a note.".into()).await?.to_string();
+
+        let resp = ts.get(&format!("/admin/note/{note_id}/delete")).send().await?;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let resp = ts.post(&format!("/admin/note/{note_id}/delete")).send().await?;
+        assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+
+        let note = ts.state.notes.by_id(&note_id).await?.expect("missing note");
+        assert_eq!(note.status, NoteStatus::Deleted);
+        assert!(ts.state.notes.most_recent(20).await?.is_empty());
+
+        let resp = ts.get("/admin/deleted").send().await?;
+        assert!(resp.text().await?.contains(&format!("/admin/note/{note_id}/purge")));
+
+        let resp = ts.get(&format!("/admin/note/{note_id}/purge")).send().await?;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let resp = ts.post(&format!("/admin/note/{note_id}/purge")).send().await?;
+        assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+        assert!(ts.state.notes.by_id(&note_id).await?.is_none());
+
+        let resp = ts.post(&format!("/admin/note/{note_id}/purge")).send().await?;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn uploading_an_image() -> Result<(), anyhow::Error> {
         let ts = TestEnv::new().await?.into_server(router()).await?;
 
         let img = fs::read("yellhole.webp").await?;
-        let form = multipart::Form::new().part(
+        let form = multipart::Form::new().text("alt_text", "A hole to yell in.").part(
             "one",
             multipart::Part::bytes(img).file_name("example.webp").mime_str("image/webp")?,
         );
@@ -171,6 +892,113 @@ values ('cbdc5a69-abba-4d75-9679-44259c48b272', 'garfield-odie-whips.bmp', 'imag
 
         let recent = ts.state.images.most_recent(1).await?;
         assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].alt_text, "A hole to yell in.");
+        assert_eq!(recent[0].status, ImageStatus::Processing);
+
+        let resp = ts.get("/admin/new").send().await?;
+        assert!(resp.text().await?.contains("Processing example.webp"));
+
+        assert_eq!(ts.state.images.process_queued().await?, 1);
+        let recent = ts.state.images.most_recent(1).await?;
+        assert_eq!(recent[0].status, ImageStatus::Ready);
+
+        let main_src = recent[0].main_src();
+        let images = ts.state.images.responsive(vec![main_src.clone()]).await?;
+        assert!(images.get(&main_src).is_some_and(|i| i.srcset.contains("300w")));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn uploading_video_and_audio() -> Result<(), anyhow::Error> {
+        let ts = TestEnv::new().await?.into_server(router()).await?;
+
+        let form = multipart::Form::new()
+            .part(
+                "one",
+                multipart::Part::bytes(b"not really a video".to_vec())
+                    .file_name("example.mp4")
+                    .mime_str("video/mp4")?,
+            )
+            .part(
+                "two",
+                multipart::Part::bytes(b"not really audio".to_vec())
+                    .file_name("example.m4a")
+                    .mime_str("audio/x-m4a")?,
+            )
+            .part(
+                "three",
+                multipart::Part::bytes(b"not a video at all".to_vec())
+                    .file_name("example.txt")
+                    .mime_str("text/plain")?,
+            );
+        let resp = ts.post("/admin/upload-images").multipart(form).send().await?;
+        assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+
+        let recent = ts.state.images.most_recent(10).await?;
+        assert_eq!(recent.len(), 2);
+        let video =
+            recent.iter().find(|i| i.content_type == "video/mp4").expect("should have a video");
+        assert_eq!(video.video_src(), Some(format!("/images/{}.video.mp4", video.image_id)));
+        assert_eq!(video.embed_src(), video.main_src());
+        let audio =
+            recent.iter().find(|i| i.content_type == "audio/x-m4a").expect("should have audio");
+        assert_eq!(audio.audio_src(), Some(format!("/images/{}.audio.m4a", audio.image_id)));
+        assert_eq!(Some(audio.embed_src()), audio.audio_src());
+
+        // Files which can't be decoded fail without leaving any files behind but the originals.
+        assert_eq!(ts.state.images.process_queued().await?, 2);
+        let recent = ts.state.images.most_recent(10).await?;
+        assert!(recent.iter().all(|i| i.status == ImageStatus::Failed));
+        assert_eq!(std::fs::read_dir(ts.state.images.images_dir())?.count(), 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn uploading_a_corrupt_image() -> Result<(), anyhow::Error> {
+        let ts = TestEnv::new().await?.into_server(router()).await?;
+
+        let form = multipart::Form::new().part(
+            "one",
+            multipart::Part::bytes(b"RIFF\x10\0\0\0WEBPVP8 not an image".to_vec())
+                .file_name("corrupt.webp")
+                .mime_str("image/webp")?,
+        );
+        let resp = ts.post("/admin/upload-images").multipart(form).send().await?;
+        assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+
+        assert_eq!(ts.state.images.process_queued().await?, 1);
+        let recent = ts.state.images.most_recent(1).await?;
+        assert_eq!(recent[0].status, ImageStatus::Failed);
+        assert!(
+            recent[0].error.as_deref().is_some_and(|e| e.starts_with("error processing image"))
+        );
+
+        // The outputs are removed, but the original is kept for retrying.
+        let data_dir = &ts.state.config.data_dir;
+        assert_eq!(std::fs::read_dir(data_dir.join("images"))?.count(), 0);
+        assert_eq!(std::fs::read_dir(data_dir.join("uploads"))?.count(), 1);
+
+        let image_id = recent[0].image_id;
+        let resp = ts.get("/admin/new").send().await?;
+        let body = resp.text().await?;
+        assert!(body.contains("Failed to process corrupt.webp"));
+        assert!(body.contains(&format!("/admin/image/{image_id}/retry")));
+
+        let resp = ts.post(&format!("/admin/image/{image_id}/retry")).send().await?;
+        assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+        let recent = ts.state.images.most_recent(1).await?;
+        assert_eq!(recent[0].status, ImageStatus::Processing);
+        assert_eq!(recent[0].error, None);
+
+        assert_eq!(ts.state.images.process_queued().await?, 1);
+        let recent = ts.state.images.most_recent(1).await?;
+        assert_eq!(recent[0].status, ImageStatus::Failed);
+
+        let resp =
+            ts.post("/admin/image/37c615b0-bb55-424d-a813-69e14ca5c20c/retry").send().await?;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
 
         Ok(())
     }
@@ -187,13 +1015,54 @@ values ('cbdc5a69-abba-4d75-9679-44259c48b272', 'garfield-odie-whips.bmp', 'imag
 
         let resp = ts
             .post("/admin/download-image")
-            .form(&[("url", ts.url.join("/logo.webp")?.to_string())])
+            .form(&[
+                ("url", ts.url.join("/logo.webp")?.to_string()),
+                ("alt_text", "A hole to yell in.".into()),
+                ("caption", "".into()),
+            ])
             .send()
             .await?;
         assert_eq!(resp.status(), StatusCode::SEE_OTHER);
 
+        assert_eq!(ts.state.images.process_queued().await?, 1);
         let recent = ts.state.images.most_recent(1).await?;
         assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].status, ImageStatus::Ready);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn adding_a_duplicate_image() -> Result<(), anyhow::Error> {
+        fn app() -> Router<AppState> {
+            Router::new()
+                .route_service("/logo.webp", get_service(ServeFile::new("yellhole.webp")))
+                .merge(router())
+        }
+
+        let ts = TestEnv::new().await?.into_server(app()).await?;
+
+        let img = fs::read("yellhole.webp").await?;
+        let form = multipart::Form::new().part(
+            "one",
+            multipart::Part::bytes(img).file_name("example.webp").mime_str("image/webp")?,
+        );
+        let resp = ts.post("/admin/upload-images").multipart(form).send().await?;
+        assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+
+        let resp = ts
+            .post("/admin/download-image")
+            .form(&[("url", ts.url.join("/logo.webp")?.to_string())])
+            .send()
+            .await?;
+        assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+
+        let recent = ts.state.images.most_recent(10).await?;
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].original_filename, "example.webp");
+        assert_eq!(ts.state.images.process_queued().await?, 1);
+        let uploads_dir = ts.state.config.data_dir.join("uploads");
+        assert_eq!(std::fs::read_dir(uploads_dir)?.count(), 1);
 
         Ok(())
     }
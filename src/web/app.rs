@@ -1,4 +1,4 @@
-use std::{any::Any, fs, io, net::SocketAddr, sync::Arc};
+use std::{any::Any, fs, net::SocketAddr, sync::Arc, time::Duration};
 
 use askama::Template;
 use axum::{
@@ -23,7 +23,10 @@ use tower_http::{
 use crate::{
     config::Config,
     services::{
-        assets::AssetService, images::ImageService, notes::NoteService, passkeys::PasskeyService,
+        assets::AssetService,
+        images::{Garbage, ImageFetcher, ImageService},
+        notes::NoteService,
+        passkeys::PasskeyService,
         sessions::SessionService,
     },
     web::{admin, asset, auth, feed},
@@ -77,14 +80,33 @@ impl App {
         // Create a new application state.
         let state = AppState::new(self.db, self.config)?;
 
+        // Index the tags of any notes written before tags were supported.
+        state.notes.backfill_tags().await?;
+
+        // Digest any images uploaded before duplicates were detected.
+        state.images.backfill_digests().await?;
+
         // Spawn a background task for deleting expired sessions.
         task::spawn(state.sessions.clone().continuously_delete_expired());
 
+        // Spawn a background task for publishing scheduled notes.
+        task::spawn(state.notes.clone().continuously_publish_scheduled());
+
+        // Spawn a background task for processing uploaded images.
+        task::spawn(state.images.clone().continuously_process(state.config.image_workers));
+
+        // Spawn a background task for collecting garbage images and files, if enabled.
+        if state.config.image_gc {
+            task::spawn(state.clone().continuously_collect_garbage());
+        }
+
         // Create a full stack of routers, state, and middleware.
+        let feed = feed::router()
+            .route_layer(middleware::from_fn_with_state(state.clone(), feed::conditional_get));
         let app = admin::router()
             .route_layer(middleware::from_fn_with_state(state.clone(), auth::require_auth))
             .merge(auth::router())
-            .merge(feed::router())
+            .merge(feed)
             .merge(asset::router(&state.images, &state.assets)?)
             .with_state(state)
             .fallback(not_found)
@@ -106,6 +128,14 @@ impl App {
 
         Ok(())
     }
+
+    /// Collect garbage images and files once, printing a report.
+    pub async fn collect_garbage(self, dry_run: bool) -> anyhow::Result<()> {
+        let state = AppState::new(self.db, self.config)?;
+        let garbage = state.collect_garbage(dry_run).await?;
+        print!("{garbage}");
+        Ok(())
+    }
 }
 
 static MIGRATIONS_DIR: Dir = include_dir!("migrations");
@@ -126,18 +156,60 @@ impl AppState {
     pub const BUILD_TIMESTAMP: &'static str = env!("BUILD_TIMESTAMP");
 
     /// Create a new [`AppState`] with the given database and config.
-    pub fn new(db: Connection, config: Config) -> Result<AppState, io::Error> {
-        let images = ImageService::new(db.clone(), &config.data_dir)?;
+    pub fn new(db: Connection, config: Config) -> Result<AppState, anyhow::Error> {
+        let fetcher = ImageFetcher::new(&config.base_url, config.image_download_allowlist.clone())?;
+        let images =
+            ImageService::new(db.clone(), &config.data_dir, config.image_processor, fetcher)?;
         let passkeys = PasskeyService::new(db.clone(), config.base_url.clone());
+        let notes = NoteService::new(db.clone(), config.time_zone.clone());
         Ok(AppState {
             config: Arc::new(config),
             assets: AssetService::new()?,
             images,
-            notes: NoteService::new(db.clone()),
+            notes,
             passkeys,
             sessions: SessionService::new(db),
         })
     }
+
+    /// Deletes images which aren't embedded in any note, originals of processed images, and files
+    /// which don't belong to any image, unless `dry_run` is set.
+    pub async fn collect_garbage(&self, dry_run: bool) -> anyhow::Result<Garbage> {
+        let base_url = &self.config.base_url;
+        let srcs = self
+            .notes
+            .all_images(base_url)
+            .await?
+            .into_iter()
+            .filter(|image| image.url.origin() == base_url.origin())
+            .map(|image| image.url.path().to_string())
+            .collect();
+        let grace_period = self
+            .config
+            .image_gc_grace_days
+            .checked_mul(24 * 60 * 60)
+            .map_or(Duration::MAX, Duration::from_secs);
+        self.images.collect_garbage(srcs, grace_period, dry_run).await
+    }
+
+    /// Runs an infinite asynchronous loop, collecting garbage every day. Failed collections are
+    /// logged and retried the next day.
+    pub async fn continuously_collect_garbage(self) -> anyhow::Result<()> {
+        let mut interval = tokio::time::interval(Duration::from_secs(24 * 60 * 60));
+        loop {
+            interval.tick().await;
+            match self.collect_garbage(false).await {
+                Ok(garbage) => tracing::info!(
+                    images = garbage.unreferenced.len(),
+                    originals = garbage.originals.len(),
+                    orphans = garbage.orphans.len(),
+                    bytes = garbage.bytes,
+                    "collected garbage"
+                ),
+                Err(err) => tracing::error!(err = format!("{err:#}"), "error collecting garbage"),
+            }
+        }
+    }
 }
 
 /// A common error type for application errors which map to responses.
@@ -154,6 +226,10 @@ pub enum AppError {
     /// When a page doesn't exist. Returns a 404.
     #[error("resource not found")]
     NotFound,
+
+    /// When a page existed but has been deleted. Returns a 410.
+    #[error("resource gone")]
+    Gone,
 }
 
 impl IntoResponse for AppError {
@@ -161,6 +237,7 @@ impl IntoResponse for AppError {
         let status = match self {
             AppError::Generic(_) | AppError::QueryFailure(_) => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::NotFound => StatusCode::NOT_FOUND,
+            AppError::Gone => StatusCode::GONE,
         };
         ErrorPage::for_status(status).into_response()
     }
@@ -1,42 +1,187 @@
-use std::{ops::Range, sync::Arc};
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    ops::Range,
+    sync::{Arc, LazyLock},
+};
 
 use askama::Template;
 use axum::{
     Router,
-    extract::{Path, Query, State},
-    http,
+    extract::{Path, Query, Request, State},
+    http::{self, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
     response::{IntoResponse, Response},
     routing::get,
 };
+use image::ImageFormat;
 use quick_xml::{
     Writer as XmlWriter,
     events::{BytesDecl, BytesText, Event},
 };
-use serde::Deserialize;
-use time::{Date, Duration, format_description::well_known::Rfc3339};
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use time::{
+    Date, Duration, Month, OffsetDateTime, PrimitiveDateTime,
+    format_description::{
+        self, BorrowedFormatItem,
+        well_known::{Iso8601, Rfc2822, Rfc3339},
+    },
+};
 use tower_http::set_header::SetResponseHeaderLayer;
 use url::Url;
 
 use crate::{
     config::Config,
-    services::notes::Note,
+    services::{
+        images::{ImagesVersion, ResponsiveImages},
+        notes::{
+            self, Cursor, Note, NoteMonth, NoteStatus, NoteTag, NotesVersion, SearchResult,
+            next_month,
+        },
+    },
     web::app::{AppError, AppState, Page},
 };
 
 pub fn router() -> Router<AppState> {
     Router::new()
-        .route("/note/{:note_id}", get(single))
-        .layer(SetResponseHeaderLayer::overriding(
-            http::header::CACHE_CONTROL,
-            http::HeaderValue::from_static("max-age=31536000,immutable"),
-        ))
         .route("/", get(index))
         .route("/atom.xml", get(atom))
-        .route("/notes/{:start}", get(week))
-        .layer(SetResponseHeaderLayer::if_not_present(
-            http::header::CACHE_CONTROL,
-            http::HeaderValue::from_static("max-age=300"),
-        ))
+        .route("/feed.json", get(json_feed))
+        .route("/rss.xml", get(rss))
+        .route("/notes/{period}", get(period))
+        .route("/notes/{period}/atom.xml", get(weekly_atom))
+        .route("/notes/{period}/{month}", get(month))
+        .route("/note/{:note_id}", get(single))
+        .route("/search", get(search))
+        .route("/tags/{tag}", get(tagged))
+        .route("/tags/{tag}/atom.xml", get(tagged_atom))
+        .layer(SetResponseHeaderLayer::if_not_present(http::header::CACHE_CONTROL, max_age()))
+}
+
+/// Answers conditional `GET` requests with `304 Not Modified` if nothing which pages and feeds are
+/// rendered from has changed since the client's copy was generated, and adds `ETag` and
+/// `Last-Modified` validators to successful responses. Every page and feed includes the archive or
+/// the most recent notes, so one validator covers all of them. `If-None-Match: *` only matches
+/// pages which exist, so it's checked after the handler runs.
+pub async fn conditional_get(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    if !matches!(*req.method(), Method::GET | Method::HEAD) {
+        return Ok(next.run(req).await);
+    }
+
+    let notes = state.notes.version().await?;
+    let images = state.images.version().await?;
+    let week = state.notes.current_week()?.start;
+    let validator = Validator::new(&state.config, notes, images, week);
+    if validator.is_fresh(req.headers()) {
+        let mut resp = StatusCode::NOT_MODIFIED.into_response();
+        validator.apply(resp.headers_mut());
+        return Ok(resp);
+    }
+
+    let any = req
+        .headers()
+        .get(http::header::IF_NONE_MATCH)
+        .and_then(|tags| tags.to_str().ok())
+        .is_some_and(|tags| tags.split(',').any(|tag| tag.trim() == "*"));
+    let mut resp = next.run(req).await;
+    if any && resp.status().is_success() {
+        resp = StatusCode::NOT_MODIFIED.into_response();
+    }
+    if matches!(resp.status(), StatusCode::OK | StatusCode::NOT_MODIFIED) {
+        validator.apply(resp.headers_mut());
+    }
+    Ok(resp)
+}
+
+/// The `ETag` and `Last-Modified` validators of the current version of the public notes, the
+/// images they embed, the configuration, the current week, and the templates which render them.
+#[derive(Debug)]
+struct Validator {
+    etag: String,
+    last_modified: OffsetDateTime,
+}
+
+impl Validator {
+    fn new(config: &Config, notes: NotesVersion, images: ImagesVersion, week: Date) -> Validator {
+        let built_at = AppState::BUILD_TIMESTAMP
+            .parse()
+            .ok()
+            .and_then(|t| OffsetDateTime::from_unix_timestamp(t).ok())
+            .unwrap_or(OffsetDateTime::UNIX_EPOCH);
+        let notes_modified_at = notes.modified_at.unwrap_or(OffsetDateTime::UNIX_EPOCH);
+        let images_modified_at = images.modified_at.unwrap_or(OffsetDateTime::UNIX_EPOCH);
+        let week_start =
+            notes::start_of_day(&config.time_zone, week).unwrap_or(OffsetDateTime::UNIX_EPOCH);
+
+        // Only the displayed parts of the configuration matter, and they can only change with a
+        // restart, so copies from before this process started serving are never fresh by date.
+        let mut hasher = DefaultHasher::new();
+        (&config.title, &config.author, &config.description, config.base_url.as_str())
+            .hash(&mut hasher);
+        format!("{:?}", config.time_zone).hash(&mut hasher);
+        static SERVING_SINCE: LazyLock<OffsetDateTime> = LazyLock::new(OffsetDateTime::now_utc);
+
+        Validator {
+            etag: format!(
+                r#"W/"{}-{:x}-{}-{}-{}-{}-{}-{}-{}""#,
+                built_at.unix_timestamp(),
+                hasher.finish(),
+                week,
+                notes_modified_at.unix_timestamp(),
+                notes.notes,
+                notes.revisions,
+                images_modified_at.unix_timestamp(),
+                images.images,
+                images.processing,
+            ),
+            last_modified: [*SERVING_SINCE, notes_modified_at, images_modified_at, week_start]
+                .into_iter()
+                .max()
+                .expect("should have a maximum")
+                .replace_nanosecond(0)
+                .expect("should be valid"),
+        }
+    }
+
+    /// Returns whether the client's copy, as described by the request's `If-None-Match` or
+    /// `If-Modified-Since` header, is still current. `If-Modified-Since` is ignored if
+    /// `If-None-Match` is present, and `If-None-Match: *` is left to [`conditional_get`].
+    fn is_fresh(&self, headers: &HeaderMap) -> bool {
+        if let Some(if_none_match) = headers.get(http::header::IF_NONE_MATCH) {
+            let weak = |etag: &str| etag.trim().trim_start_matches("W/").to_string();
+            return if_none_match
+                .to_str()
+                .is_ok_and(|tags| tags.split(',').any(|tag| weak(tag) == weak(&self.etag)));
+        }
+        headers
+            .get(http::header::IF_MODIFIED_SINCE)
+            .and_then(|since| since.to_str().ok())
+            .and_then(|since| PrimitiveDateTime::parse(since, &http_date()).ok())
+            .is_some_and(|since| self.last_modified <= since.assume_utc())
+    }
+
+    /// Adds the validators and caching policy to the given response headers.
+    fn apply(&self, headers: &mut HeaderMap) {
+        let last_modified = self.last_modified.format(&http_date()).expect("should format");
+        headers.insert(http::header::ETAG, self.etag.parse().expect("should be a valid header"));
+        headers.insert(
+            http::header::LAST_MODIFIED,
+            last_modified.parse().expect("should be a valid header"),
+        );
+        headers.entry(http::header::CACHE_CONTROL).or_insert(max_age());
+    }
+}
+
+/// The format of HTTP dates, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`.
+fn http_date() -> Vec<BorrowedFormatItem<'static>> {
+    format_description::parse(
+        "[weekday repr:short], [day] [month repr:short] [year] [hour]:[minute]:[second] GMT",
+    )
+    .expect("should be a valid format")
 }
 
 #[derive(Debug, Template)]
@@ -44,36 +189,95 @@ pub fn router() -> Router<AppState> {
 struct FeedPage {
     config: Arc<Config>,
     notes: Vec<Note>,
+    responsive_images: ResponsiveImages,
+    archive: Vec<ArchiveYear>,
+    tags: Vec<NoteTag>,
+    search: Option<Search>,
+    older: Option<Url>,
+    newer: Option<Url>,
+}
+
+#[derive(Debug)]
+struct Search {
+    query: String,
+    results: Vec<SearchResult>,
+}
+
+/// A year in the archive navigation.
+#[derive(Debug)]
+struct ArchiveYear {
+    year: i32,
+    count: u32,
+    months: Vec<ArchiveMonth>,
+}
+
+/// A month in the archive navigation, with the weeks which overlap it.
+#[derive(Debug)]
+struct ArchiveMonth {
+    month: NoteMonth,
     weeks: Vec<Range<Date>>,
 }
 
 impl FeedPage {
-    fn new(state: AppState, notes: Vec<Note>, weeks: Vec<Range<Date>>) -> FeedPage {
-        FeedPage { config: state.config, notes, weeks }
+    async fn new(state: AppState, notes: Vec<Note>) -> Result<FeedPage, AppError> {
+        let weeks = state.notes.weeks().await?;
+        let months = state.notes.months().await?;
+        let tags = state.notes.tags().await?;
+        let srcs = notes.iter().flat_map(Note::image_srcs).collect();
+        let responsive_images = state.images.responsive(srcs).await?;
+        Ok(FeedPage {
+            config: state.config,
+            notes,
+            responsive_images,
+            archive: archive(months, &weeks),
+            tags,
+            search: None,
+            older: None,
+            newer: None,
+        })
+    }
+}
+
+/// Groups the given months by year and each month's overlapping weeks under it.
+fn archive(months: Vec<NoteMonth>, weeks: &[Range<Date>]) -> Vec<ArchiveYear> {
+    let mut years: Vec<ArchiveYear> = Vec::new();
+    for month in months {
+        let weeks = weeks
+            .iter()
+            .filter(|w| w.start < month.end() && month.start < w.end)
+            .cloned()
+            .collect::<Vec<_>>();
+        let (year, count) = (month.start.year(), month.count);
+        let month = ArchiveMonth { month, weeks };
+        match years.last_mut() {
+            Some(y) if y.year == year => {
+                y.count += count;
+                y.months.push(month);
+            }
+            _ => years.push(ArchiveYear { year, count, months: vec![month] }),
+        }
     }
+    years
 }
 
-mod filters {
+pub(super) mod filters {
     use askama::{Error::Custom, Result};
-    use time::{Date, OffsetDateTime, UtcOffset, format_description::well_known::Rfc3339};
+    use time::{Date, OffsetDateTime, format_description::well_known::Rfc3339};
+    use tz::TimeZone;
     use url::Url;
 
-    use crate::services::notes::Note;
+    use crate::services::notes::{self, Note};
 
     pub fn to_rfc3339(t: &OffsetDateTime, _: &dyn askama::Values) -> Result<String> {
         t.format(&Rfc3339).map_err(|e| Custom(Box::new(e)))
     }
 
-    pub fn to_local_tz(t: &OffsetDateTime, _: &dyn askama::Values) -> Result<OffsetDateTime> {
-        let local = tz::TimeZone::local()
-            .map_err(|e| Custom(Box::new(e)))?
-            .find_current_local_time_type()
-            .map_err(|e| Custom(Box::new(e)))?
-            .ut_offset();
-        Ok(t.checked_to_offset(
-            UtcOffset::from_whole_seconds(local).map_err(|e| Custom(Box::new(e)))?,
-        )
-        .expect("should convert"))
+    pub fn to_local_tz(
+        t: &OffsetDateTime,
+        _: &dyn askama::Values,
+        time_zone: &TimeZone,
+    ) -> Result<OffsetDateTime> {
+        notes::to_local(time_zone, *t).map_err(|e| Custom(e.into()))
     }
 
     pub fn to_note_url(note: &Note, _: &dyn askama::Values, base_url: &Url) -> Result<Url> {
@@ -84,126 +288,688 @@ mod filters {
         super::to_atom_url(base_url).map_err(|e| Custom(Box::new(e)))
     }
 
+    pub fn to_json_feed_url(base_url: &Url, _: &dyn askama::Values) -> Result<Url> {
+        super::to_json_feed_url(base_url).map_err(|e| Custom(Box::new(e)))
+    }
+
+    pub fn to_rss_url(base_url: &Url, _: &dyn askama::Values) -> Result<Url> {
+        super::to_rss_url(base_url).map_err(|e| Custom(Box::new(e)))
+    }
+
     pub fn to_weekly_url(week: &Date, _: &dyn askama::Values, base_url: &Url) -> Result<Url> {
+        super::to_weekly_url(week, base_url).map_err(|e| Custom(Box::new(e)))
+    }
+
+    pub fn to_monthly_url(month: &Date, _: &dyn askama::Values, base_url: &Url) -> Result<Url> {
+        base_url
+            .join("notes/")
+            .and_then(|u| u.join(&format!("{}/{:02}", month.year(), u8::from(month.month()))))
+            .map_err(|e| Custom(Box::new(e)))
+    }
+
+    pub fn to_yearly_url(year: &i32, _: &dyn askama::Values, base_url: &Url) -> Result<Url> {
         base_url
             .join("notes/")
-            .and_then(|u| u.join(&week.to_string()))
+            .and_then(|u| u.join(&year.to_string()))
             .map_err(|e| Custom(Box::new(e)))
     }
+
+    pub fn to_tag_url(tag: &str, _: &dyn askama::Values, base_url: &Url) -> Result<Url> {
+        super::to_tag_url(tag, base_url).map_err(|e| Custom(Box::new(e)))
+    }
 }
 
 fn to_atom_url(base_url: &Url) -> Result<Url, url::ParseError> {
     base_url.join("atom.xml")
 }
 
+fn to_json_feed_url(base_url: &Url) -> Result<Url, url::ParseError> {
+    base_url.join("feed.json")
+}
+
+fn to_rss_url(base_url: &Url) -> Result<Url, url::ParseError> {
+    base_url.join("rss.xml")
+}
+
+fn to_weekly_url(week: &Date, base_url: &Url) -> Result<Url, url::ParseError> {
+    base_url.join("notes/").and_then(|u| u.join(&week.to_string()))
+}
+
+fn to_weekly_atom_url(week: &Date, base_url: &Url) -> Result<Url, url::ParseError> {
+    base_url.join("notes/").and_then(|u| u.join(&format!("{week}/atom.xml")))
+}
+
+fn to_tag_url(tag: &str, base_url: &Url) -> Result<Url, url::ParseError> {
+    base_url.join("tags/").and_then(|u| u.join(&tag.to_lowercase()))
+}
+
+fn to_tag_atom_url(tag: &str, base_url: &Url) -> Result<Url, url::ParseError> {
+    base_url.join("tags/").and_then(|u| u.join(&format!("{}/atom.xml", tag.to_lowercase())))
+}
+
 fn to_note_url(note: &Note, base_url: &Url) -> Result<Url, url::ParseError> {
     base_url.join("note/").and_then(|u| u.join(&note.note_id.to_string()))
 }
 
+#[serde_as]
 #[derive(Debug, Deserialize)]
 struct IndexOpts {
     n: Option<u16>,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    before: Option<Cursor>,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    after: Option<Cursor>,
 }
 
+/// The default number of notes on a page of the feed.
+const PAGE_SIZE: u16 = 25;
+
+/// The maximum number of notes on a page of the feed.
+const MAX_PAGE_SIZE: u16 = 100;
+
 async fn index(
     State(state): State<AppState>,
-    opts: Query<IndexOpts>,
+    Query(opts): Query<IndexOpts>,
+) -> Result<Response, AppError> {
+    let n = opts.n.unwrap_or(PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+
+    // Fetch one more note than needed to see if there's another page in that direction.
+    let (notes, has_older, has_newer) = if let Some(after) = opts.after {
+        let mut notes = state.notes.after(after, n + 1).await?;
+        let has_newer = notes.len() > n.into();
+        if has_newer {
+            notes.remove(0);
+        }
+        (notes, true, has_newer)
+    } else {
+        let mut notes = state.notes.before(opts.before, n + 1).await?;
+        let has_older = notes.len() > n.into();
+        notes.truncate(n.into());
+        (notes, has_older, opts.before.is_some())
+    };
+
+    let page_url = |param: &str, cursor: Cursor| {
+        let mut url = state.config.base_url.clone();
+        url.query_pairs_mut().append_pair(param, &cursor.to_string());
+        if let Some(n) = opts.n {
+            url.query_pairs_mut().append_pair("n", &n.to_string());
+        }
+        url
+    };
+    let older = notes.last().filter(|_| has_older).map(|n| page_url("before", n.cursor()));
+    let newer = notes.first().filter(|_| has_newer).map(|n| page_url("after", n.cursor()));
+
+    let links = [
+        older.as_ref().map(|u| format!(r#"<{u}>; rel="next""#)),
+        newer.as_ref().map(|u| format!(r#"<{u}>; rel="prev""#)),
+    ]
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>()
+    .join(", ");
+
+    let page = Page(FeedPage { older, newer, ..FeedPage::new(state, notes).await? });
+    if links.is_empty() {
+        Ok(page.into_response())
+    } else {
+        Ok(([(http::header::LINK, links)], page).into_response())
+    }
+}
+
+/// Shows either the week starting on a date (e.g. `/notes/2022-10-09`) or a year (e.g.
+/// `/notes/2022`).
+async fn period(
+    State(state): State<AppState>,
+    period: Option<Path<String>>,
 ) -> Result<Page<FeedPage>, AppError> {
-    let weeks = state.notes.weeks().await?;
-    let notes = state.notes.most_recent(opts.n.unwrap_or(25)).await?;
-    Ok(Page(FeedPage::new(state, notes, weeks)))
+    let period = period.ok_or(AppError::NotFound)?.0;
+    let range = if let Ok(year) = period.parse::<i32>() {
+        let start =
+            Date::from_calendar_date(year, Month::January, 1).map_err(|_| AppError::NotFound)?;
+        let end = Date::from_calendar_date(year + 1, Month::January, 1)
+            .map_err(|_| AppError::NotFound)?;
+        start..end
+    } else {
+        let start = Date::parse(&period, &Iso8601::DATE).map_err(|_| AppError::NotFound)?;
+        let end = start.checked_add(Duration::days(7)).ok_or(AppError::NotFound)?;
+        start..end
+    };
+    let notes = state.notes.date_range(range).await?;
+    Ok(Page(FeedPage::new(state, notes).await?))
 }
 
-async fn week(
+async fn month(
     State(state): State<AppState>,
-    start: Option<Path<Date>>,
+    period: Option<Path<(i32, u8)>>,
 ) -> Result<Page<FeedPage>, AppError> {
-    let weeks = state.notes.weeks().await?;
-    let start = start.ok_or(AppError::NotFound)?.0;
-    let end = start.checked_add(Duration::days(7)).expect("should allow week addition");
+    let (year, month) = period.ok_or(AppError::NotFound)?.0;
+    let month = Month::try_from(month).map_err(|_| AppError::NotFound)?;
+    let start = Date::from_calendar_date(year, month, 1).map_err(|_| AppError::NotFound)?;
+    let end = next_month(start).ok_or(AppError::NotFound)?;
     let notes = state.notes.date_range(start..end).await?;
-    Ok(Page(FeedPage::new(state, notes, weeks)))
+    Ok(Page(FeedPage::new(state, notes).await?))
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchOpts {
+    q: Option<String>,
+}
+
+async fn search(
+    State(state): State<AppState>,
+    Query(opts): Query<SearchOpts>,
+) -> Result<Page<FeedPage>, AppError> {
+    let query = opts.q.unwrap_or_default();
+    let results = state.notes.search(&query, 50).await?;
+    Ok(Page(FeedPage {
+        search: Some(Search { query, results }),
+        ..FeedPage::new(state, vec![]).await?
+    }))
+}
+
+async fn tagged(
+    State(state): State<AppState>,
+    Path(tag): Path<String>,
+) -> Result<Page<FeedPage>, AppError> {
+    let notes = state.notes.tagged(&tag, 100).await?;
+    Ok(Page(FeedPage::new(state, notes).await?))
 }
 
 async fn single(
     State(state): State<AppState>,
     note_id: Option<Path<String>>,
 ) -> Result<Page<FeedPage>, AppError> {
-    let weeks = state.notes.weeks().await?;
     let note_id = note_id.ok_or(AppError::NotFound)?;
-    let notes = vec![state.notes.by_id(&note_id).await?.ok_or(AppError::NotFound)?];
-    Ok(Page(FeedPage::new(state, notes, weeks)))
+    let note = state.notes.by_id(&note_id).await?.ok_or(AppError::NotFound)?;
+    match note.status {
+        NoteStatus::Published => Ok(Page(FeedPage::new(state, vec![note]).await?)),
+        NoteStatus::Draft | NoteStatus::Scheduled => Err(AppError::NotFound),
+        NoteStatus::Deleted => Err(AppError::Gone),
+    }
 }
 
+/// Shows the most recent notes as the subscription document of an archived feed (RFC 5005). The
+/// document links to the archive of the most recent complete week, and includes every note from the
+/// current week so that nothing falls between it and the archives.
 async fn atom(State(state): State<AppState>) -> Result<Response, AppError> {
+    let mut notes = state.notes.most_recent(20).await?;
+    let current_week = state.notes.current_week()?;
+    let this_week = state.notes.date_range(current_week.clone()).await?;
+    if this_week.len() > notes.len() {
+        notes = this_week;
+    }
+    let prev = state.notes.weeks().await?.into_iter().find(|w| w.end <= current_week.start);
+
+    let base_url = &state.config.base_url;
+    let atom_url = to_atom_url(base_url).expect("should be a valid URL");
+    let (title, home_url) = (state.config.title.clone(), base_url.clone());
+    let mut feed = FeedDocument::new(&state, title, home_url, atom_url, notes).await?;
+    feed.history.prev =
+        prev.map(|w| to_weekly_atom_url(&w.start, base_url).expect("should be a valid URL"));
+    feed.to_atom()
+}
+
+/// Shows the notes of the week starting on a date (e.g. `/notes/2022-10-09/atom.xml`) as an
+/// archive document of an archived feed (RFC 5005). Only complete weeks are archived, so archive
+/// documents don't change as notes are published.
+async fn weekly_atom(
+    State(state): State<AppState>,
+    week: Option<Path<String>>,
+) -> Result<Response, AppError> {
+    let start = Date::parse(&week.ok_or(AppError::NotFound)?.0, &Iso8601::DATE)
+        .map_err(|_| AppError::NotFound)?;
+    let current_week = state.notes.current_week()?;
+    let weeks = state
+        .notes
+        .weeks()
+        .await?
+        .into_iter()
+        .filter(|w| w.end <= current_week.start)
+        .collect::<Vec<_>>();
+    let i = weeks.iter().position(|w| w.start == start).ok_or(AppError::NotFound)?;
+    let notes = state.notes.date_range(weeks[i].clone()).await?;
+
+    let base_url = &state.config.base_url;
+    let week_url = |w: &Range<Date>| to_weekly_atom_url(&w.start, base_url).ok();
+    let home_url = to_weekly_url(&start, base_url).expect("should be a valid URL");
+    let atom_url = to_weekly_atom_url(&start, base_url).expect("should be a valid URL");
+    let title = format!("{} (week of {start})", state.config.title);
+    let mut feed = FeedDocument::new(&state, title, home_url, atom_url, notes).await?;
+    feed.history = FeedHistory {
+        archive: true,
+        current: to_atom_url(base_url).ok(),
+        // Weeks are in reverse chronological order.
+        prev: weeks.get(i + 1).and_then(week_url),
+        next: i.checked_sub(1).and_then(|j| week_url(&weeks[j])),
+    };
+    feed.to_atom()
+}
+
+async fn json_feed(State(state): State<AppState>) -> Result<Response, AppError> {
     let notes = state.notes.most_recent(20).await?;
-    let atom_url = to_atom_url(&state.config.base_url).expect("should be a valid URL");
-    let mut xml = XmlWriter::new(Vec::<u8>::with_capacity(1024));
-    xml.write_event(Event::Decl(BytesDecl::new("1.0", None, None))).map_err(anyhow::Error::new)?;
-    xml.create_element("feed")
-        .with_attributes([
-            ("xmlns", "http://www.w3.org/2005/Atom"),
-            ("xml:base", atom_url.as_str()),
-        ])
-        .write_inner_content(|feed| {
-            feed.create_element("title")
-                .write_text_content(BytesText::new(&state.config.title))?
-                .create_element("id")
-                .write_text_content(BytesText::new(state.config.base_url.as_str()))?;
-
-            feed.create_element("author")
-                .write_inner_content(|author| {
-                    author
-                        .create_element("name")
-                        .write_text_content(BytesText::new(&state.config.author))?;
-                    Ok(())
-                })?
-                .create_element("link")
-                .with_attributes([("href", atom_url.as_str()), ("rel", "alternate")])
-                .write_empty()?
-                .create_element("subtitle")
-                .write_text_content(BytesText::new(&state.config.description))?;
-
-            if !notes.is_empty() {
-                feed.create_element("updated").write_text_content(BytesText::new(
-                    &notes[0].created_at.format(&Rfc3339).expect("should format"),
-                ))?;
-            }
+    let feed_url = to_json_feed_url(&state.config.base_url).expect("should be a valid URL");
+    let (title, home_url) = (state.config.title.clone(), state.config.base_url.clone());
+    FeedDocument::new(&state, title, home_url, feed_url, notes).await?.to_json()
+}
+
+async fn rss(State(state): State<AppState>) -> Result<Response, AppError> {
+    let notes = state.notes.most_recent(20).await?;
+    let rss_url = to_rss_url(&state.config.base_url).expect("should be a valid URL");
+    let (title, home_url) = (state.config.title.clone(), state.config.base_url.clone());
+    FeedDocument::new(&state, title, home_url, rss_url, notes).await?.to_rss()
+}
+
+async fn tagged_atom(
+    State(state): State<AppState>,
+    Path(tag): Path<String>,
+) -> Result<Response, AppError> {
+    let notes = state.notes.tagged(&tag, 20).await?;
+    let tag_url = to_tag_url(&tag, &state.config.base_url).expect("should be a valid URL");
+    let atom_url = to_tag_atom_url(&tag, &state.config.base_url).expect("should be a valid URL");
+    let title = format!("{} #{}", state.config.title, tag.to_lowercase());
+    FeedDocument::new(&state, title, tag_url, atom_url, notes).await?.to_atom()
+}
+
+/// A syndication feed of notes, which can be written as Atom, JSON Feed, or RSS.
+#[derive(Debug)]
+struct FeedDocument {
+    title: String,
+    /// The URL of the HTML page the feed corresponds to, also used as the feed's ID.
+    home_url: Url,
+    /// The URL of the feed itself.
+    feed_url: Url,
+    author: String,
+    description: String,
+    /// When the most recently modified note was modified, if there are any notes.
+    updated: Option<OffsetDateTime>,
+    entries: Vec<FeedEntry>,
+    history: FeedHistory,
+}
+
+/// Where a [`FeedDocument`] sits in an archived feed (RFC 5005).
+#[derive(Debug, Default)]
+struct FeedHistory {
+    /// Whether the document is an archive document, whose contents don't change.
+    archive: bool,
+    /// The URL of the subscription document.
+    current: Option<Url>,
+    /// The URL of the next older archive document.
+    prev: Option<Url>,
+    /// The URL of the next newer archive document.
+    next: Option<Url>,
+}
+
+/// A note in a [`FeedDocument`].
+#[derive(Debug)]
+struct FeedEntry {
+    /// The URL of the note, also used as the entry's ID.
+    url: Url,
+    /// A plain-text title derived from the note's first heading or description.
+    title: String,
+    published: OffsetDateTime,
+    updated: OffsetDateTime,
+    content_html: String,
+    summary: String,
+    /// The first image in the note, if any.
+    image: Option<Url>,
+    /// The videos and audio in the note.
+    attachments: Vec<FeedAttachment>,
+    /// The note's hashtags.
+    tags: Vec<String>,
+}
+
+/// A video or audio file in a [`FeedEntry`].
+#[derive(Debug)]
+struct FeedAttachment {
+    url: Url,
+    content_type: String,
+    size: Option<u64>,
+}
+
+impl FeedDocument {
+    async fn new(
+        state: &AppState,
+        title: String,
+        home_url: Url,
+        feed_url: Url,
+        notes: Vec<Note>,
+    ) -> Result<FeedDocument, AppError> {
+        let config = &state.config;
+        let images =
+            state.images.responsive(notes.iter().flat_map(Note::image_srcs).collect()).await?;
+        let entries = notes
+            .iter()
+            .map(|note| FeedEntry {
+                url: to_note_url(note, &config.base_url).expect("should be a valid URL"),
+                title: note.title(&config.time_zone),
+                published: note.created_at,
+                updated: note.modified_at(),
+                content_html: note.to_html(&images),
+                summary: note.description(),
+                image: note
+                    .images(&config.base_url)
+                    .into_iter()
+                    .map(|image| image.url)
+                    .find(|url| images.audio(url.path()).is_none()),
+                attachments: note
+                    .image_srcs()
+                    .iter()
+                    .filter_map(|src| images.media(src))
+                    .map(|media| FeedAttachment {
+                        url: config.base_url.join(&media.src).expect("should be a valid URL"),
+                        content_type: media.content_type.clone(),
+                        size: media.size,
+                    })
+                    .collect(),
+                tags: note.tags(),
+            })
+            .collect();
+        Ok(FeedDocument {
+            title,
+            home_url,
+            feed_url,
+            author: config.author.clone(),
+            description: config.description.clone(),
+            updated: notes.iter().map(Note::modified_at).max(),
+            entries,
+            history: FeedHistory::default(),
+        })
+    }
+
+    /// Writes the feed as an Atom feed.
+    fn to_atom(&self) -> Result<Response, AppError> {
+        let mut xml = XmlWriter::new(Vec::<u8>::with_capacity(1024));
+        xml.write_event(Event::Decl(BytesDecl::new("1.0", None, None)))
+            .map_err(anyhow::Error::new)?;
+        xml.create_element("feed")
+            .with_attributes([
+                ("xmlns", "http://www.w3.org/2005/Atom"),
+                ("xmlns:fh", "http://purl.org/syndication/history/1.0"),
+                ("xml:base", self.feed_url.as_str()),
+            ])
+            .write_inner_content(|feed| {
+                feed.create_element("title")
+                    .write_text_content(BytesText::new(&self.title))?
+                    .create_element("id")
+                    .write_text_content(BytesText::new(self.home_url.as_str()))?;
+
+                feed.create_element("author")
+                    .write_inner_content(|author| {
+                        author
+                            .create_element("name")
+                            .write_text_content(BytesText::new(&self.author))?;
+                        Ok(())
+                    })?
+                    .create_element("link")
+                    .with_attributes([("href", self.feed_url.as_str()), ("rel", "self")])
+                    .write_empty()?
+                    .create_element("link")
+                    .with_attributes([("href", self.home_url.as_str()), ("rel", "alternate")])
+                    .write_empty()?
+                    .create_element("subtitle")
+                    .write_text_content(BytesText::new(&self.description))?;
+
+                if self.history.archive {
+                    feed.create_element("fh:archive").write_empty()?;
+                }
+                for (rel, href) in [
+                    ("current", &self.history.current),
+                    ("prev-archive", &self.history.prev),
+                    ("next-archive", &self.history.next),
+                ] {
+                    if let Some(href) = href {
+                        feed.create_element("link")
+                            .with_attributes([("href", href.as_str()), ("rel", rel)])
+                            .write_empty()?;
+                    }
+                }
+
+                if let Some(updated) = self.updated {
+                    feed.create_element("updated").write_text_content(BytesText::new(
+                        &updated.format(&Rfc3339).expect("should format"),
+                    ))?;
+                }
+
+                for entry in &self.entries {
+                    feed.create_element("entry").write_inner_content(|xml| {
+                        xml.create_element("title")
+                            .write_text_content(BytesText::new(&entry.title))?
+                            .create_element("id")
+                            .write_text_content(BytesText::new(entry.url.as_str()))?
+                            .create_element("published")
+                            .write_text_content(BytesText::new(
+                                &entry.published.format(&Rfc3339).expect("should format"),
+                            ))?
+                            .create_element("updated")
+                            .write_text_content(BytesText::new(
+                                &entry.updated.format(&Rfc3339).expect("should format"),
+                            ))?
+                            .create_element("author")
+                            .write_inner_content(|author| {
+                                author
+                                    .create_element("name")
+                                    .write_text_content(BytesText::new(&self.author))?;
+                                Ok(())
+                            })?
+                            .create_element("link")
+                            .with_attributes([("href", entry.url.as_str()), ("rel", "alternate")])
+                            .write_empty()?;
+                        for tag in &entry.tags {
+                            xml.create_element("category")
+                                .with_attributes([
+                                    ("term", tag.as_str()),
+                                    ("label", &format!("#{tag}")),
+                                ])
+                                .write_empty()?;
+                        }
+                        // Videos and audio are enclosures, so podcast clients can download them.
+                        for attachment in &entry.attachments {
+                            let mut link = xml.create_element("link").with_attributes([
+                                ("href", attachment.url.as_str()),
+                                ("rel", "enclosure"),
+                                ("type", attachment.content_type.as_str()),
+                            ]);
+                            if let Some(size) = attachment.size {
+                                link = link.with_attribute(("length", size.to_string().as_str()));
+                            }
+                            link.write_empty()?;
+                        }
+                        xml.create_element("summary")
+                            .write_text_content(BytesText::new(&entry.summary))?
+                            .create_element("content")
+                            .with_attribute(("type", "html"))
+                            .write_text_content(BytesText::new(&entry.content_html))?;
+                        Ok(())
+                    })?;
+                }
 
-            for note in notes {
-                let url =
-                    to_note_url(&note, &state.config.base_url).expect("should be a valid URL");
-                feed.create_element("entry").write_inner_content(|entry| {
-                    entry
+                Ok(())
+            })
+            .map_err(anyhow::Error::new)?;
+
+        Ok(([(http::header::CONTENT_TYPE, atom_xml())], xml.into_inner()).into_response())
+    }
+
+    /// Writes the feed as an RSS 2.0 feed.
+    fn to_rss(&self) -> Result<Response, AppError> {
+        let mut xml = XmlWriter::new(Vec::<u8>::with_capacity(1024));
+        xml.write_event(Event::Decl(BytesDecl::new("1.0", None, None)))
+            .map_err(anyhow::Error::new)?;
+        xml.create_element("rss")
+            .with_attributes([("version", "2.0"), ("xmlns:atom", "http://www.w3.org/2005/Atom")])
+            .write_inner_content(|rss| {
+                rss.create_element("channel").write_inner_content(|channel| {
+                    channel
                         .create_element("title")
-                        .write_text_content(BytesText::new(&note.note_id.to_string()))?
-                        .create_element("id")
-                        .write_text_content(BytesText::new(url.as_str()))?
-                        .create_element("updated")
-                        .write_text_content(BytesText::new(
-                            &note.created_at.format(&Rfc3339).expect("should format"),
-                        ))?
+                        .write_text_content(BytesText::new(&self.title))?
                         .create_element("link")
-                        .with_attributes([("href", url.as_str()), ("rel", "alternate")])
-                        .write_empty()?
-                        .create_element("content")
-                        .with_attribute(("type", "html"))
-                        .write_text_content(BytesText::new(&note.to_html()))?;
+                        .write_text_content(BytesText::new(self.home_url.as_str()))?
+                        .create_element("description")
+                        .write_text_content(BytesText::new(&self.description))?
+                        .create_element("atom:link")
+                        .with_attributes([
+                            ("href", self.feed_url.as_str()),
+                            ("rel", "self"),
+                            ("type", "application/rss+xml"),
+                        ])
+                        .write_empty()?;
+
+                    if let Some(updated) = self.updated {
+                        channel.create_element("lastBuildDate").write_text_content(
+                            BytesText::new(&updated.format(&Rfc2822).expect("should format")),
+                        )?;
+                    }
+
+                    for entry in &self.entries {
+                        channel.create_element("item").write_inner_content(|item| {
+                            item.create_element("title")
+                                .write_text_content(BytesText::new(&entry.title))?
+                                .create_element("link")
+                                .write_text_content(BytesText::new(entry.url.as_str()))?
+                                .create_element("guid")
+                                .with_attribute(("isPermaLink", "true"))
+                                .write_text_content(BytesText::new(entry.url.as_str()))?
+                                .create_element("pubDate")
+                                .write_text_content(BytesText::new(
+                                    &entry.published.format(&Rfc2822).expect("should format"),
+                                ))?
+                                .create_element("description")
+                                .write_text_content(BytesText::new(&entry.content_html))?;
+
+                            // RSS items only have one enclosure, so prefer a video or audio file
+                            // over the first image. An unknown length is given as zero.
+                            let enclosure = match (entry.attachments.first(), &entry.image) {
+                                (Some(attachment), _) => Some((
+                                    &attachment.url,
+                                    attachment.content_type.as_str(),
+                                    attachment.size.unwrap_or(0),
+                                )),
+                                (None, Some(image)) => Some((
+                                    image,
+                                    ImageFormat::from_path(image.path())
+                                        .map_or("application/octet-stream", |f| f.to_mime_type()),
+                                    0,
+                                )),
+                                (None, None) => None,
+                            };
+                            if let Some((url, content_type, length)) = enclosure {
+                                item.create_element("enclosure")
+                                    .with_attributes([
+                                        ("url", url.as_str()),
+                                        ("length", length.to_string().as_str()),
+                                        ("type", content_type),
+                                    ])
+                                    .write_empty()?;
+                            }
+                            Ok(())
+                        })?;
+                    }
                     Ok(())
                 })?;
-            }
+                Ok(())
+            })
+            .map_err(anyhow::Error::new)?;
 
-            Ok(())
-        })
-        .map_err(anyhow::Error::new)?;
+        Ok(([(http::header::CONTENT_TYPE, rss_xml())], xml.into_inner()).into_response())
+    }
+
+    /// Writes the feed as a JSON Feed 1.1 feed.
+    fn to_json(&self) -> Result<Response, AppError> {
+        let rfc3339 = |t: OffsetDateTime| t.format(&Rfc3339).expect("should format");
+        let feed = JsonFeed {
+            version: "https://jsonfeed.org/version/1.1",
+            title: &self.title,
+            home_page_url: &self.home_url,
+            feed_url: &self.feed_url,
+            description: &self.description,
+            authors: [JsonFeedAuthor { name: &self.author }],
+            items: self
+                .entries
+                .iter()
+                .map(|entry| JsonFeedItem {
+                    id: &entry.url,
+                    url: &entry.url,
+                    title: &entry.title,
+                    content_html: &entry.content_html,
+                    summary: &entry.summary,
+                    image: entry.image.as_ref(),
+                    date_published: rfc3339(entry.published),
+                    date_modified: rfc3339(entry.updated),
+                    attachments: entry
+                        .attachments
+                        .iter()
+                        .map(|attachment| JsonFeedAttachment {
+                            url: &attachment.url,
+                            mime_type: &attachment.content_type,
+                            size_in_bytes: attachment.size,
+                        })
+                        .collect(),
+                })
+                .collect(),
+        };
+        let json = serde_json::to_vec(&feed).map_err(anyhow::Error::new)?;
+        Ok(([(http::header::CONTENT_TYPE, feed_json())], json).into_response())
+    }
+}
+
+/// A [JSON Feed](https://www.jsonfeed.org/version/1.1/) document.
+#[derive(Debug, Serialize)]
+struct JsonFeed<'a> {
+    version: &'static str,
+    title: &'a str,
+    home_page_url: &'a Url,
+    feed_url: &'a Url,
+    description: &'a str,
+    authors: [JsonFeedAuthor<'a>; 1],
+    items: Vec<JsonFeedItem<'a>>,
+}
 
-    Ok(([(http::header::CONTENT_TYPE, atom_xml())], xml.into_inner()).into_response())
+#[derive(Debug, Serialize)]
+struct JsonFeedAuthor<'a> {
+    name: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonFeedItem<'a> {
+    id: &'a Url,
+    url: &'a Url,
+    title: &'a str,
+    content_html: &'a str,
+    summary: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    image: Option<&'a Url>,
+    date_published: String,
+    date_modified: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    attachments: Vec<JsonFeedAttachment<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonFeedAttachment<'a> {
+    url: &'a Url,
+    mime_type: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size_in_bytes: Option<u64>,
 }
 
 const fn atom_xml() -> http::HeaderValue {
     http::HeaderValue::from_static("application/atom+xml; charset=utf-8")
 }
 
+const fn max_age() -> HeaderValue {
+    HeaderValue::from_static("max-age=300")
+}
+
+const fn rss_xml() -> http::HeaderValue {
+    http::HeaderValue::from_static("application/rss+xml; charset=utf-8")
+}
+
+const fn feed_json() -> http::HeaderValue {
+    http::HeaderValue::from_static("application/feed+json; charset=utf-8")
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::Cursor;
@@ -259,14 +1025,491 @@ values ('b0a2170c-5e91-42ad-aa1b-dabc3c6ea5b9', 'Ok, I *guess* this is fine.', '
         );
 
         let feed = Feed::read_from(Cursor::new(&resp.bytes().await?)).expect("should parse");
+        assert!(
+            feed.links()
+                .iter()
+                .any(|l| l.rel() == "self" && l.href() == "http://example.com/atom.xml")
+        );
+        let entry = |id: &str| {
+            let id = format!("http://example.com/note/{id}");
+            feed.entries().iter().find(|e| e.id() == id).expect("should have an entry")
+        };
+
+        let mario = entry("69b124f0-a4fa-40d0-83f4-06bc4213f3ca");
+        assert_eq!(mario.title().as_str(), "It’s a me, Mario.");
         assert_eq!(
-            feed.entries[0]
-                .content()
-                .expect("should have content")
-                .value()
-                .expect("should have a value"),
+            mario.content().expect("should have content").value().expect("should have a value"),
             "<p>It’s a me, <em>Mario</em>.</p>\n"
         );
+        assert_eq!(mario.summary().map(|s| s.as_str()), Some("It’s a me, Mario."));
+        assert_eq!(
+            mario.published().map(|t| t.to_rfc3339()).as_deref(),
+            Some("2022-11-14T18:22:00+00:00")
+        );
+        assert_eq!(mario.authors()[0].name(), ts.state.config.author);
+        assert!(mario.categories().is_empty());
+
+        let header = entry("c1449d6c-6b5b-4ce4-a4d7-98853562fbf1");
+        assert_eq!(header.title().as_str(), "Hello, it is a header.");
+
+        let note_id =
+            ts.state.notes.create(format!("{} #Luigi #wahoo", "Let's-a go! ".repeat(10))).await?;
+        let resp = ts.get("/atom.xml").send().await?;
+        let feed = Feed::read_from(Cursor::new(&resp.bytes().await?)).expect("should parse");
+        let luigi = feed
+            .entries()
+            .iter()
+            .find(|e| e.id() == format!("http://example.com/note/{note_id}"))
+            .expect("should have an entry");
+        assert_eq!(
+            luigi.title().as_str(),
+            "Let’s-a go! Let’s-a go! Let’s-a go! Let’s-a go! Let’s-a go! Let’s-a go! Let’s-a…"
+        );
+        assert_eq!(
+            luigi.categories().iter().map(|c| c.term()).collect::<Vec<_>>(),
+            vec!["luigi", "wahoo"]
+        );
+        assert_eq!(luigi.categories()[0].label(), Some("#luigi"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn archived_atom_feeds() -> Result<(), anyhow::Error> {
+        let ts = TestEnv::new().await?.into_server(router()).await?;
+        note_fixtures(&ts).await?;
+        ts.state.notes.create("Still this week.".into()).await?;
+        let link = |feed: &Feed, rel: &str| {
+            feed.links().iter().find(|l| l.rel() == rel).map(|l| l.href().to_string())
+        };
+
+        let resp = ts.get("/atom.xml").send().await?;
+        let feed = Feed::read_from(Cursor::new(&resp.bytes().await?)).expect("should parse");
+        assert_eq!(feed.entries.len(), 4);
+        assert!(!feed.extensions().contains_key("fh"));
+        assert_eq!(
+            link(&feed, "prev-archive").as_deref(),
+            Some("http://example.com/notes/2022-11-13/atom.xml")
+        );
+
+        let resp = ts.get("/notes/2022-10-09/atom.xml").send().await?;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let feed = Feed::read_from(Cursor::new(&resp.bytes().await?)).expect("should parse");
+        assert_eq!(feed.entries.len(), 1);
+        assert_eq!(
+            feed.entries[0].id(),
+            "http://example.com/note/c1449d6c-6b5b-4ce4-a4d7-98853562fbf1"
+        );
+        assert!(feed.extensions().get("fh").is_some_and(|e| e.contains_key("archive")));
+        assert_eq!(link(&feed, "current").as_deref(), Some("http://example.com/atom.xml"));
+        assert_eq!(
+            link(&feed, "prev-archive").as_deref(),
+            Some("http://example.com/notes/2022-09-04/atom.xml")
+        );
+        assert_eq!(
+            link(&feed, "next-archive").as_deref(),
+            Some("http://example.com/notes/2022-11-13/atom.xml")
+        );
+
+        let resp = ts.get("/notes/2022-11-13/atom.xml").send().await?;
+        let feed = Feed::read_from(Cursor::new(&resp.bytes().await?)).expect("should parse");
+        assert_eq!(link(&feed, "next-archive"), None);
+
+        // Only the starts of complete weeks with notes are archived.
+        let resp = ts.get("/notes/2022-10-10/atom.xml").send().await?;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let current_week = ts.state.notes.current_week()?;
+        let resp = ts.get(&format!("/notes/{}/atom.xml", current_week.start)).send().await?;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        // The weekly pages are unaffected.
+        let resp = ts.get("/notes/2022-10-09").send().await?;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn conditional_gets() -> Result<(), anyhow::Error> {
+        let ts = TestEnv::new().await?;
+        let app = router()
+            .route_layer(axum::middleware::from_fn_with_state(ts.state.clone(), conditional_get));
+        let ts = ts.into_server(app).await?;
+        note_fixtures(&ts).await?;
+        let note_id = "69b124f0-a4fa-40d0-83f4-06bc4213f3ca";
+        let paths = ["/", "/atom.xml", "/notes/2022-10-09/atom.xml", &format!("/note/{note_id}")];
+
+        let mut validators = Vec::new();
+        for path in paths {
+            let resp = ts.get(path).send().await?;
+            assert_eq!(resp.status(), StatusCode::OK);
+            let etag = resp.headers().get(header::ETAG).expect("should have an ETag").clone();
+            assert!(etag.as_bytes().starts_with(b"W/\""));
+            let last_modified =
+                resp.headers().get(header::LAST_MODIFIED).expect("should be dated").clone();
+            assert_eq!(
+                resp.headers().get(header::CACHE_CONTROL).map(|h| h.as_bytes()),
+                Some(max_age().as_bytes())
+            );
+
+            let resp = ts.get(path).header(header::IF_NONE_MATCH, &etag).send().await?;
+            assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+            assert_eq!(resp.headers().get(header::ETAG), Some(&etag));
+
+            let resp =
+                ts.get(path).header(header::IF_MODIFIED_SINCE, &last_modified).send().await?;
+            assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+
+            // If-None-Match takes precedence over If-Modified-Since.
+            let resp = ts
+                .get(path)
+                .header(header::IF_NONE_MATCH, r#"W/"nope""#)
+                .header(header::IF_MODIFIED_SINCE, &last_modified)
+                .send()
+                .await?;
+            assert_eq!(resp.status(), StatusCode::OK);
+
+            validators.push(etag);
+        }
+
+        // Editing a note invalidates every page and feed.
+        assert!(ts.state.notes.update(note_id, "It's a me, Luigi.".into()).await?);
+        for (path, etag) in paths.iter().zip(&validators) {
+            let resp = ts.get(path).header(header::IF_NONE_MATCH, etag).send().await?;
+            assert_eq!(resp.status(), StatusCode::OK);
+        }
+
+        // As does deleting one.
+        let resp = ts.get("/atom.xml").send().await?;
+        let etag = resp.headers().get(header::ETAG).expect("should have an ETag").clone();
+        assert!(ts.state.notes.delete(note_id).await?);
+        let resp = ts.get("/atom.xml").header(header::IF_NONE_MATCH, &etag).send().await?;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        // As does publishing a scheduled note, even one backdated to before the last change.
+        let resp = ts.get("/atom.xml").send().await?;
+        let last_modified =
+            resp.headers().get(header::LAST_MODIFIED).expect("should be dated").clone();
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        let publish_at = OffsetDateTime::now_utc() - Duration::days(365);
+        ts.state.notes.schedule(None, "Better late than never.".into(), publish_at).await?;
+        assert_eq!(ts.state.notes.publish_scheduled().await?, 1);
+        let resp =
+            ts.get("/atom.xml").header(header::IF_MODIFIED_SINCE, &last_modified).send().await?;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        // Processing an image changes how it's rendered.
+        let mut png = Vec::new();
+        image::RgbImage::new(10, 10).write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+        let body = futures::stream::iter([Ok::<_, std::io::Error>(axum::body::Bytes::from(png))]);
+        let png = ts.state.images.add("a.png".into(), mime::IMAGE_PNG, "".into(), "".into(), body);
+        png.await?;
+        let resp = ts.get("/").send().await?;
+        let etag = resp.headers().get(header::ETAG).expect("should have an ETag").clone();
+        assert_eq!(ts.state.images.process_queued().await?, 1);
+        let resp = ts.get("/").header(header::IF_NONE_MATCH, &etag).send().await?;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        // So does the week rolling over, which changes the archive links.
+        let notes = ts.state.notes.version().await?;
+        let images = ts.state.images.version().await?;
+        let week = ts.state.notes.current_week()?.start;
+        let this_week = Validator::new(&ts.state.config, notes, images, week);
+        let next_week = Validator::new(&ts.state.config, notes, images, week + Duration::weeks(1));
+        assert_ne!(this_week.etag, next_week.etag);
+        assert!(this_week.last_modified < next_week.last_modified);
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, this_week.etag.parse()?);
+        assert!(this_week.is_fresh(&headers));
+        assert!(!next_week.is_fresh(&headers));
+
+        // Missing pages aren't given validators.
+        let resp = ts.get("/notes/2022-10-10/atom.xml").send().await?;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert!(resp.headers().get(header::ETAG).is_none());
+
+        // If-None-Match: * only matches pages which exist.
+        let resp = ts.get("/").header(header::IF_NONE_MATCH, "*").send().await?;
+        assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+        assert!(resp.headers().get(header::ETAG).is_some());
+        let resp =
+            ts.get("/notes/2022-10-10/atom.xml").header(header::IF_NONE_MATCH, "*").send().await?;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let resp = ts.get(&format!("/note/{note_id}")).header(header::IF_NONE_MATCH, "*").send();
+        assert_eq!(resp.await?.status(), StatusCode::GONE);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn json_feed() -> Result<(), anyhow::Error> {
+        let ts = TestEnv::new().await?.into_server(router()).await?;
+        note_fixtures(&ts).await?;
+
+        let resp = ts.get("/feed.json").send().await?;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).map(|h| h.as_bytes()),
+            Some(feed_json().as_bytes())
+        );
+
+        let feed = resp.json::<serde_json::Value>().await?;
+        assert_eq!(feed["version"], "https://jsonfeed.org/version/1.1");
+        assert_eq!(feed["home_page_url"], "http://example.com/");
+        assert_eq!(feed["feed_url"], "http://example.com/feed.json");
+        assert_eq!(feed["authors"][0]["name"], ts.state.config.author);
+        assert_eq!(feed["items"].as_array().map(Vec::len), Some(3));
+
+        let item = &feed["items"][0];
+        assert_eq!(item["id"], "http://example.com/note/69b124f0-a4fa-40d0-83f4-06bc4213f3ca");
+        assert_eq!(item["content_html"], "<p>It’s a me, <em>Mario</em>.</p>\n");
+        assert_eq!(item["summary"], "It’s a me, Mario.");
+        assert_eq!(item["date_published"], "2022-11-14T18:22:00Z");
+        assert!(item.get("image").is_none());
+
+        let resp = ts.get("/").send().await?;
+        assert!(resp.text().await?.contains(r#"href="http://example.com/feed.json""#));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rss_feed() -> Result<(), anyhow::Error> {
+        let ts = TestEnv::new().await?.into_server(router()).await?;
+        note_fixtures(&ts).await?;
+        ts.state
+            .notes
+            .create("![Garfield](/images/garfield.main.webp) <b>& friends</b>".into())
+            .await?;
+
+        let resp = ts.get("/rss.xml").send().await?;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).map(|h| h.as_bytes()),
+            Some(rss_xml().as_bytes())
+        );
+
+        let channel = rss::Channel::read_from(Cursor::new(&resp.bytes().await?))?;
+        assert_eq!(channel.link(), "http://example.com/");
+        assert_eq!(
+            channel.namespaces().get("atom").map(String::as_str),
+            Some("http://www.w3.org/2005/Atom")
+        );
+        assert_eq!(channel.items().len(), 4);
+
+        let item = &channel.items()[0];
+        assert!(item.description().is_some_and(|d| d.contains("<b>&amp; friends</b>")));
+        let enclosure = item.enclosure().expect("should have an enclosure");
+        assert_eq!(enclosure.url(), "http://example.com/images/garfield.main.webp");
+        assert_eq!(enclosure.mime_type(), "image/webp");
+        assert_eq!(enclosure.length(), "0");
+
+        let item = &channel.items()[1];
+        let guid = item.guid().expect("should have a GUID");
+        assert!(guid.is_permalink());
+        assert_eq!(guid.value(), "http://example.com/note/69b124f0-a4fa-40d0-83f4-06bc4213f3ca");
+        assert_eq!(item.link(), Some(guid.value()));
+        assert_eq!(item.pub_date(), Some("Mon, 14 Nov 2022 18:22:00 +0000"));
+        assert_eq!(item.description(), Some("<p>It’s a me, <em>Mario</em>.</p>"));
+        assert!(item.enclosure().is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn edited_note() -> Result<(), anyhow::Error> {
+        let ts = TestEnv::new().await?.into_server(router()).await?;
+        note_fixtures(&ts).await?;
+        ts.state
+            .notes
+            .update("69b124f0-a4fa-40d0-83f4-06bc4213f3ca", "It's a me, _Luigi_.".into())
+            .await?;
+
+        let resp = ts.get("/note/69b124f0-a4fa-40d0-83f4-06bc4213f3ca").send().await?;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CACHE_CONTROL).map(|h| h.as_bytes()),
+            Some("max-age=300".as_bytes())
+        );
+
+        let body = resp.text().await?;
+        assert!(body.contains("Luigi"));
+        assert!(body.contains("edited"));
+
+        let resp = ts.get("/atom.xml").send().await?;
+        let feed = Feed::read_from(Cursor::new(&resp.bytes().await?)).expect("should parse");
+        assert!(feed.entries[0].updated() > feed.entries[1].updated());
+        assert_eq!(feed.updated(), feed.entries[0].updated());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn deleted_note() -> Result<(), anyhow::Error> {
+        let ts = TestEnv::new().await?.into_server(router()).await?;
+        note_fixtures(&ts).await?;
+        ts.state.notes.delete("c1449d6c-6b5b-4ce4-a4d7-98853562fbf1").await?;
+
+        let resp = ts.get("/note/c1449d6c-6b5b-4ce4-a4d7-98853562fbf1").send().await?;
+        assert_eq!(resp.status(), StatusCode::GONE);
+
+        let resp = ts.get("/").send().await?;
+        assert!(!resp.text().await?.contains("Hello, it is a header"));
+
+        let resp = ts.get("/notes/2022-10-09").send().await?;
+        assert!(!resp.text().await?.contains("Hello, it is a header"));
+
+        let resp = ts.get("/atom.xml").send().await?;
+        let feed = Feed::read_from(Cursor::new(&resp.bytes().await?)).expect("should parse");
+        assert_eq!(feed.entries.len(), 2);
+
+        assert_eq!(ts.state.notes.weeks().await?.len(), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn draft_note() -> Result<(), anyhow::Error> {
+        let ts = TestEnv::new().await?.into_server(router()).await?;
+        note_fixtures(&ts).await?;
+        let draft_id = ts.state.notes.save_draft(None, "Not ready for _primetime_.".into()).await?;
+
+        let resp = ts.get(&format!("/note/{draft_id}")).send().await?;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let resp = ts.get("/").send().await?;
+        assert!(!resp.text().await?.contains("primetime"));
+
+        let resp = ts.get("/atom.xml").send().await?;
+        let feed = Feed::read_from(Cursor::new(&resp.bytes().await?)).expect("should parse");
+        assert_eq!(feed.entries.len(), 3);
+
+        assert_eq!(ts.state.notes.weeks().await?.len(), 3);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn tagged_notes() -> Result<(), anyhow::Error> {
+        let ts = TestEnv::new().await?.into_server(router()).await?;
+        note_fixtures(&ts).await?;
+        ts.state.notes.create("Mamma mia! #Mario #Luigi".into()).await?;
+        ts.state.notes.create("Wahoo! #mario".into()).await?;
+
+        let resp = ts.get("/").send().await?;
+        let body = resp.text().await?;
+        assert!(body.contains(r#"<a href="/tags/mario">#Mario</a>"#));
+        assert!(body.contains(r#"<a href="http://example.com/tags/mario">#mario (2)</a>"#));
+
+        let resp = ts.get("/tags/mario").send().await?;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.text().await?;
+        assert!(body.contains("Mamma mia!"));
+        assert!(body.contains("Wahoo!"));
+        assert!(!body.contains("Hello, it is a header"));
+
+        let resp = ts.get("/tags/luigi/atom.xml").send().await?;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let feed = Feed::read_from(Cursor::new(&resp.bytes().await?)).expect("should parse");
+        assert_eq!(feed.title().as_str(), "Yellhole #luigi");
+        assert_eq!(feed.entries.len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn backfilling_tags() -> Result<(), anyhow::Error> {
+        let ts = TestEnv::new().await?.into_server(router()).await?;
+        ts.db
+            .call_unwrap(|conn| {
+                conn.execute_batch(
+                    r#"
+                    insert into note (note_id, body, created_at)
+                    values ('69b124f0-a4fa-40d0-83f4-06bc4213f3ca', 'Old #mario', '2022-11-14 18:22:00');
+                    "#,
+                )
+            })
+            .await?;
+        assert!(ts.state.notes.tagged("mario", 10).await?.is_empty());
+
+        assert!(ts.state.notes.backfill_tags().await?);
+        assert_eq!(ts.state.notes.tagged("mario", 10).await?.len(), 1);
+
+        // Later startups don't reindex anything.
+        ts.db.call_unwrap(|conn| conn.execute_batch("delete from note_tag")).await?;
+        assert!(!ts.state.notes.backfill_tags().await?);
+        assert!(ts.state.notes.tagged("mario", 10).await?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn searching() -> Result<(), anyhow::Error> {
+        let ts = TestEnv::new().await?.into_server(router()).await?;
+        note_fixtures(&ts).await?;
+        ts.state.notes.save_draft(None, "A secret mario draft.".into()).await?;
+        let note_id = ts.state.notes.create("Not about plumbers.".into()).await?.to_string();
+        ts.state.notes.update(&note_id, "About a plumber named Mario.".into()).await?;
+
+        let resp = ts.get("/search?q=mario").send().await?;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.text().await?;
+        assert!(body.contains("a me, _<mark>Mario</mark>_."));
+        assert!(body.contains("About a plumber named <mark>Mario</mark>."));
+        assert!(!body.contains("secret"));
+
+        let resp = ts.get("/search?q=%22%20OR%20NOT").send().await?;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        ts.state.notes.purge(&note_id).await?;
+        assert_eq!(ts.state.notes.search("plumber", 10).await?.len(), 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn pagination() -> Result<(), anyhow::Error> {
+        let ts = TestEnv::new().await?.into_server(router()).await?;
+        note_fixtures(&ts).await?;
+
+        fn link(resp: &reqwest::Response, rel: &str) -> Option<String> {
+            let links = resp.headers().get(header::LINK)?.to_str().ok()?;
+            links.split(", ").find_map(|l| {
+                l.strip_suffix(&format!(r#">; rel="{rel}""#))?.strip_prefix('<').map(Into::into)
+            })
+        }
+
+        let resp = ts.get("/?n=1").send().await?;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(link(&resp, "prev").is_none());
+        let next = link(&resp, "next").expect("should have a next page");
+        let body = resp.text().await?;
+        assert!(body.contains("Mario"));
+        assert!(!body.contains("Hello, it is a header"));
+        assert!(body.contains("Older"));
+
+        let resp = ts.get(&next.replace("http://example.com/", "/")).send().await?;
+        let prev = link(&resp, "prev").expect("should have a previous page");
+        let next = link(&resp, "next").expect("should have a next page");
+        let body = resp.text().await?;
+        assert!(!body.contains("Mario"));
+        assert!(body.contains("Hello, it is a header"));
+
+        let resp = ts.get(&next.replace("http://example.com/", "/")).send().await?;
+        assert!(link(&resp, "next").is_none());
+        assert!(resp.text().await?.contains("I <em>guess</em> this is fine"));
+
+        let resp = ts.get(&prev.replace("http://example.com/", "/")).send().await?;
+        assert!(link(&resp, "prev").is_none());
+        assert!(link(&resp, "next").is_some());
+        assert!(resp.text().await?.contains("Mario"));
+
+        let resp = ts.get("/?n=65535").send().await?;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(link(&resp, "next").is_none());
+
+        let resp = ts.get("/?before=garbage").send().await?;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
 
         Ok(())
     }
@@ -285,6 +1528,109 @@ values ('b0a2170c-5e91-42ad-aa1b-dabc3c6ea5b9', 'Ok, I *guess* this is fine.', '
         Ok(())
     }
 
+    #[tokio::test]
+    async fn monthly_and_yearly_views() -> Result<(), anyhow::Error> {
+        let ts = TestEnv::new().await?.into_server(router()).await?;
+        note_fixtures(&ts).await?;
+
+        let resp = ts.get("/notes/2022").send().await?;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.text().await?;
+        assert!(body.contains("Mario"));
+        assert!(body.contains("Hello, it is a header"));
+        assert!(body.contains("I <em>guess</em> this is fine"));
+        assert!(body.contains("<summary>2022 (3)</summary>"));
+        assert!(body.contains("<summary>October (1)</summary>"));
+        assert!(body.contains(r#"<a href="http://example.com/notes/2022/10">"#));
+
+        let resp = ts.get("/notes/2022/10").send().await?;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.text().await?;
+        assert!(!body.contains("Mario"));
+        assert!(body.contains("Hello, it is a header"));
+
+        let resp = ts.get("/notes/2021").send().await?;
+        assert!(resp.text().await?.contains("Nothing here yet."));
+
+        let resp = ts.get("/notes/2022/13").send().await?;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let resp = ts.get("/notes/9999/12").send().await?;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let resp = ts.get("/notes/october").send().await?;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let months = ts.state.notes.months().await?;
+        assert_eq!(
+            months.iter().map(|m| (m.start.to_string(), m.count)).collect::<Vec<_>>(),
+            vec![
+                ("2022-11-01".to_string(), 1),
+                ("2022-10-01".to_string(), 1),
+                ("2022-09-01".to_string(), 1)
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn archive_time_zone() -> Result<(), anyhow::Error> {
+        let ts = TestEnv::new().await?.into_server(router()).await?;
+        ts.db
+            .call_unwrap(|conn| {
+                conn.execute_batch(
+                    r#"
+insert into note (note_id, body, created_at)
+values ('69b124f0-a4fa-40d0-83f4-06bc4213f3ca', 'Saturday night.', '2022-11-06 05:30:00');
+
+insert into note (note_id, body, created_at)
+values ('c1449d6c-6b5b-4ce4-a4d7-98853562fbf1', 'Halloween.', '2022-11-01 03:00:00');
+        "#,
+                )
+            })
+            .await?;
+
+        let utc = ts.state.notes.weeks().await?;
+        assert_eq!(
+            utc.iter().map(|w| w.start.to_string()).collect::<Vec<_>>(),
+            vec!["2022-11-06", "2022-10-30"]
+        );
+
+        let tz = tz::TimeZone::from_posix_tz("MST7MDT,M3.2.0,M11.1.0")?;
+        let notes = crate::services::notes::NoteService::new(ts.db.clone(), tz);
+
+        let weeks = notes.weeks().await?;
+        assert_eq!(
+            weeks.iter().map(|w| w.start.to_string()).collect::<Vec<_>>(),
+            vec!["2022-10-30"]
+        );
+
+        let months = notes.months().await?;
+        assert_eq!(
+            months.iter().map(|m| (m.start.to_string(), m.count)).collect::<Vec<_>>(),
+            vec![("2022-11-01".to_string(), 1), ("2022-10-01".to_string(), 1)]
+        );
+
+        let start = Date::from_calendar_date(2022, Month::November, 6)?;
+        assert!(
+            notes
+                .date_range(start..next_month(start).expect("should be a valid date"))
+                .await?
+                .is_empty()
+        );
+        let start = Date::from_calendar_date(2022, Month::October, 1)?;
+        assert_eq!(
+            notes
+                .date_range(start..next_month(start).expect("should be a valid date"))
+                .await?
+                .len(),
+            1
+        );
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn single_note() -> Result<(), anyhow::Error> {
         let ts = TestEnv::new().await?.into_server(router()).await?;
@@ -299,6 +1645,171 @@ values ('b0a2170c-5e91-42ad-aa1b-dabc3c6ea5b9', 'Ok, I *guess* this is fine.', '
         Ok(())
     }
 
+    #[tokio::test]
+    async fn image_metadata() -> Result<(), anyhow::Error> {
+        let ts = TestEnv::new().await?.into_server(router()).await?;
+        let note_id =
+            ts.state.notes.create("![A hole to yell in.](/images/hole.webp)".into()).await?;
+
+        let resp = ts.get(&format!("/note/{note_id}")).send().await?;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let body = resp.text().await?;
+        assert!(body.contains(
+            r#"<meta property="og:image" content="http://example.com/images/hole.webp">"#
+        ));
+        assert!(body.contains(r#"<meta property="og:image:alt" content="A hole to yell in.">"#));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn responsive_images() -> Result<(), anyhow::Error> {
+        let ts = TestEnv::new().await?.into_server(router()).await?;
+        ts.db
+            .call_unwrap(|conn| {
+                conn.execute_batch(
+                    r#"
+insert into image (image_id, original_filename, content_type, width, height)
+values ('4c89cfef-9031-49c0-8b91-2578c0e227f3', 'garfield.jpg', 'image/jpeg', 800, 400);
+
+insert into image_variant (image_id, width)
+values ('4c89cfef-9031-49c0-8b91-2578c0e227f3', 300), ('4c89cfef-9031-49c0-8b91-2578c0e227f3', 600);
+
+insert into image (image_id, original_filename, content_type)
+values ('7963d8bc-9cf8-4459-a593-b6d49b94b541', 'odie.jpg', 'image/jpeg');
+"#,
+                )
+            })
+            .await?;
+        let note_id = ts
+            .state
+            .notes
+            .create(
+                concat!(
+                    "![Garfield & lasagna](/images/4c89cfef-9031-49c0-8b91-2578c0e227f3.main.webp)\n\n",
+                    "![Odie](/images/7963d8bc-9cf8-4459-a593-b6d49b94b541.main.webp)"
+                )
+                .into(),
+            )
+            .await?;
+
+        let resp = ts.get(&format!("/note/{note_id}")).send().await?;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let body = resp.text().await?;
+        assert!(body.contains(concat!(
+            r#"<img src="/images/4c89cfef-9031-49c0-8b91-2578c0e227f3.main.webp" "#,
+            r#"srcset="/images/4c89cfef-9031-49c0-8b91-2578c0e227f3.300w.webp 300w, "#,
+            r#"/images/4c89cfef-9031-49c0-8b91-2578c0e227f3.600w.webp 600w" "#,
+            r#"sizes="(max-width: 600px) 100vw, 600px" width="600" height="300" "#,
+            r#"alt="Garfield &amp; lasagna" />"#
+        )));
+        assert!(body.contains(
+            r#"<img src="/images/7963d8bc-9cf8-4459-a593-b6d49b94b541.main.webp" alt="Odie" />"#
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn videos() -> Result<(), anyhow::Error> {
+        let ts = TestEnv::new().await?.into_server(router()).await?;
+        ts.db
+            .call_unwrap(|conn| {
+                conn.execute_batch(
+                    r#"
+insert into image (image_id, original_filename, content_type, width, height)
+values ('2f1e4a8c-5b6d-4e7f-8a9b-0c1d2e3f4a5b', 'nermal.mov', 'video/quicktime', 1920, 1080);
+
+insert into image_variant (image_id, width)
+values ('2f1e4a8c-5b6d-4e7f-8a9b-0c1d2e3f4a5b', 300);
+"#,
+                )
+            })
+            .await?;
+        let note_id = ts
+            .state
+            .notes
+            .create("![Nermal](/images/2f1e4a8c-5b6d-4e7f-8a9b-0c1d2e3f4a5b.main.webp)".into())
+            .await?;
+
+        let resp = ts.get(&format!("/note/{note_id}")).send().await?;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.text().await?;
+        assert!(body.contains(concat!(
+            r#"<video controls playsinline preload="metadata" "#,
+            r#"poster="/images/2f1e4a8c-5b6d-4e7f-8a9b-0c1d2e3f4a5b.main.webp" "#,
+            r#"width="600" height="337" aria-label="Nermal">"#,
+            r#"<source src="/images/2f1e4a8c-5b6d-4e7f-8a9b-0c1d2e3f4a5b.video.mov" "#,
+            r#"type="video/quicktime" />"#,
+        )));
+
+        let resp = ts.get("/atom.xml").send().await?;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(resp.text().await?.contains(concat!(
+            r#"<link href="http://example.com/images/2f1e4a8c-5b6d-4e7f-8a9b-0c1d2e3f4a5b.video.mov" "#,
+            r#"rel="enclosure" type="video/quicktime"/>"#
+        )));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn audio() -> Result<(), anyhow::Error> {
+        let ts = TestEnv::new().await?.into_server(router()).await?;
+        ts.db
+            .call_unwrap(|conn| {
+                conn.execute_batch(
+                    r#"
+insert into image (image_id, original_filename, content_type, duration, size)
+values ('9a8b7c6d-5e4f-4a3b-8c2d-1e0f9a8b7c6d', 'memo.mp3', 'audio/mpeg', 62.5, 12345);
+"#,
+                )
+            })
+            .await?;
+        let note_id = ts
+            .state
+            .notes
+            .create(
+                "![A voice memo](/images/9a8b7c6d-5e4f-4a3b-8c2d-1e0f9a8b7c6d.audio.mp3)".into(),
+            )
+            .await?;
+
+        let resp = ts.get(&format!("/note/{note_id}")).send().await?;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.text().await?;
+        assert!(body.contains(concat!(
+            r#"<audio controls preload="metadata" "#,
+            r#"src="/images/9a8b7c6d-5e4f-4a3b-8c2d-1e0f9a8b7c6d.audio.mp3" aria-label="A voice memo">"#,
+        )));
+        assert!(body.contains(concat!(
+            r#"<meta property="og:audio" "#,
+            r#"content="http://example.com/images/9a8b7c6d-5e4f-4a3b-8c2d-1e0f9a8b7c6d.audio.mp3">"#
+        )));
+        assert!(!body.contains("og:image"));
+
+        let resp = ts.get("/atom.xml").send().await?;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(resp.text().await?.contains(concat!(
+            r#"<link href="http://example.com/images/9a8b7c6d-5e4f-4a3b-8c2d-1e0f9a8b7c6d.audio.mp3" "#,
+            r#"rel="enclosure" type="audio/mpeg" length="12345"/>"#
+        )));
+
+        let resp = ts.get("/feed.json").send().await?;
+        let feed = resp.json::<serde_json::Value>().await?;
+        let attachment = &feed["items"][0]["attachments"][0];
+        assert_eq!(
+            attachment["url"],
+            "http://example.com/images/9a8b7c6d-5e4f-4a3b-8c2d-1e0f9a8b7c6d.audio.mp3"
+        );
+        assert_eq!(attachment["mime_type"], "audio/mpeg");
+        assert_eq!(attachment["size_in_bytes"], 12345);
+        assert!(feed["items"][0].get("image").is_none());
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn bad_note_id() -> Result<(), anyhow::Error> {
         let ts = TestEnv::new().await?.into_server(router()).await?;